@@ -0,0 +1,32 @@
+use crate::types::camera_controller::{offset, Camera, CameraController, CameraInput};
+use crate::types::view_state::ViewState;
+
+/// Walks on the horizontal plane at a fixed eye height and looks around with the mouse.
+pub struct FirstPersonController {
+    camera: Camera,
+}
+
+impl FirstPersonController {
+    pub fn new(view_state: &ViewState) -> FirstPersonController {
+        FirstPersonController { camera: Camera::from_view_state(view_state) }
+    }
+}
+
+impl CameraController for FirstPersonController {
+    fn name(&self) -> &'static str {
+        "FIRST PERSON"
+    }
+
+    fn attach(&mut self, view_state: &ViewState) {
+        self.camera = Camera::from_view_state(view_state);
+    }
+
+    fn update(&mut self, input: &CameraInput, delta_time: f32) -> Camera {
+        self.camera.turn((input.look.0 + input.pointer.0, input.look.1 + input.pointer.1));
+        let (right, _, backward) = input.movement;
+        let (sin_yaw, cos_yaw) = self.camera.yaw.sin_cos();
+        let eye = offset(self.camera.eye, self.camera.right(), right * delta_time);
+        self.camera.eye = offset(eye, (sin_yaw, 0.0, cos_yaw), backward * delta_time);
+        self.camera
+    }
+}
@@ -0,0 +1,32 @@
+use crate::types::camera_controller::{offset, Camera, CameraController, CameraInput};
+use crate::types::view_state::ViewState;
+
+/// Free flight: moves along where the camera looks and turns while the camera is dragged.
+pub struct FlyController {
+    camera: Camera,
+}
+
+impl FlyController {
+    pub fn new(view_state: &ViewState) -> FlyController {
+        FlyController { camera: Camera::from_view_state(view_state) }
+    }
+}
+
+impl CameraController for FlyController {
+    fn name(&self) -> &'static str {
+        "FLY"
+    }
+
+    fn attach(&mut self, view_state: &ViewState) {
+        self.camera = Camera::from_view_state(view_state);
+    }
+
+    fn update(&mut self, input: &CameraInput, delta_time: f32) -> Camera {
+        self.camera.turn(input.look);
+        let (right, up, backward) = input.movement;
+        let eye = offset(self.camera.eye, self.camera.right(), right * delta_time);
+        let eye = offset(eye, self.camera.up(), up * delta_time);
+        self.camera.eye = offset(eye, self.camera.forward(), -backward * delta_time);
+        self.camera
+    }
+}
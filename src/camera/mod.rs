@@ -0,0 +1,3 @@
+pub mod orbit;
+pub mod fly;
+pub mod first_person;
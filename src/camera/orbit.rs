@@ -0,0 +1,50 @@
+use crate::types::camera_controller::{offset, Camera, CameraController, CameraInput};
+use crate::types::view_state::ViewState;
+
+const MIN_DISTANCE: f32 = 10.0;
+
+/// Circles a target point: dragging orbits around it, moving forward and backward zooms and
+/// moving sideways or up and down pans the target.
+pub struct OrbitController {
+    camera: Camera,
+    target: (f32, f32, f32),
+    distance: f32,
+}
+
+impl OrbitController {
+    pub fn new(view_state: &ViewState) -> OrbitController {
+        let mut controller = OrbitController {
+            camera: Camera::from_view_state(view_state),
+            target: (0.0, 0.0, 0.0),
+            distance: MIN_DISTANCE,
+        };
+        controller.attach(view_state);
+        controller
+    }
+}
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &'static str {
+        "ORBIT"
+    }
+
+    /// Targets the point in front of the camera that is as far as the reference point.
+    fn attach(&mut self, view_state: &ViewState) {
+        self.camera = Camera::from_view_state(view_state);
+        let (fx, fy, fz) = self.camera.forward();
+        let (ex, ey, ez) = self.camera.eye;
+        let to_ref = (view_state.ref_x - ex, view_state.ref_y - ey, view_state.ref_z - ez);
+        self.distance = (to_ref.0 * fx + to_ref.1 * fy + to_ref.2 * fz).max(MIN_DISTANCE);
+        self.target = offset(self.camera.eye, self.camera.forward(), self.distance);
+    }
+
+    fn update(&mut self, input: &CameraInput, delta_time: f32) -> Camera {
+        self.camera.turn(input.look);
+        let (right, up, backward) = input.movement;
+        self.target = offset(self.target, self.camera.right(), right * delta_time);
+        self.target = offset(self.target, self.camera.up(), up * delta_time);
+        self.distance = (self.distance + backward * delta_time).max(MIN_DISTANCE);
+        self.camera.eye = offset(self.target, self.camera.forward(), -self.distance);
+        self.camera
+    }
+}
@@ -0,0 +1,356 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::graphics::gpu_renderer::AdapterOptions;
+use crate::types::{light::Light, view_state::ViewState};
+use super::options::{Backend, Command, Options};
+
+pub const CONFIG_FILE: &str = "engine.toml";
+
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// Frame rate cap, 0 for none.
+    pub fps: f32,
+}
+
+pub struct InputConfig {
+    pub bindings: PathBuf,
+    pub acceleration: f32,
+    pub damping: f32,
+    pub smoothing: f32,
+}
+
+pub struct StreamConfig {
+    /// Memory for the streamed chunks the renderer holds, in MiB.
+    pub budget: f32,
+}
+
+impl StreamConfig {
+    pub fn budget_bytes(&self) -> usize {
+        (self.budget as f64 * 1024.0 * 1024.0) as usize
+    }
+}
+
+/// Runtime settings, read from a TOML file and overridden by command-line options:
+///
+/// ```text
+/// [window]
+/// width = 1280
+/// vsync = false
+///
+/// [renderer]
+/// backend = "surface"
+/// gpu_backends = "vulkan,gl"
+///
+/// [camera]
+/// camera_z = 900.0
+///
+/// [lighting]
+/// intensity = 800.0
+///
+/// [input]
+/// bindings = "my_bindings.txt"
+///
+/// [stream]
+/// budget = 512.0
+/// ```
+pub struct Config {
+    pub window: WindowConfig,
+    pub backend: Backend,
+    pub adapter: AdapterOptions,
+    /// Whether to switch to the cpu backend when no GPU adapter can be used, instead of failing.
+    pub fallback: bool,
+    /// The starting view, its scale being the projection scale at the configured window size.
+    pub view_state: ViewState,
+    pub light: Light,
+    pub input: InputConfig,
+    pub stream: StreamConfig,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            window: WindowConfig {
+                width: 800,
+                height: 600,
+                fullscreen: false,
+                vsync: true,
+                fps: 23.0,
+            },
+            backend: Backend::Gpu,
+            adapter: AdapterOptions::default(),
+            fallback: true,
+            view_state: ViewState {
+                angle_x: 0.0,
+                angle_y: 0.0,
+                angle_z: 25.0,
+                c_angle_x: 0.0,
+                c_angle_y: 0.0,
+                c_angle_z: 0.0,
+                l_angle_x: 0.0,
+                l_angle_y: 0.0,
+                l_angle_z: 0.0,
+                camera_x: 0.0,
+                camera_y: 0.0,
+                camera_z: 700.0,
+                ref_x: 0.0,
+                ref_y: 0.0,
+                ref_z: 0.0,
+                z_offset: 50.0,
+                scale: 1000.0,
+            },
+            light: Light {
+                x: 0.0,
+                y: 0.0,
+                z: 700.0,
+                intensity: 500.0,
+            },
+            input: InputConfig {
+                bindings: PathBuf::from("bindings.txt"),
+                acceleration: 400.0,
+                damping: 8.0,
+                smoothing: 0.0,
+            },
+            stream: StreamConfig {
+                budget: 256.0,
+            },
+        }
+    }
+
+    /// Reads the file given with `--config`, or engine.toml when there is one, then applies the
+    /// command-line options on top.
+    pub fn resolve(options: &Options) -> io::Result<Config> {
+        let mut config = match &options.config {
+            Some(path) => Config::load(path)?,
+            None if Path::new(CONFIG_FILE).is_file() => Config::load(Path::new(CONFIG_FILE))?,
+            None => Config::new(),
+        };
+        config.apply(options);
+        Ok(config)
+    }
+
+    /// Reads settings over the defaults, leaving out ones the file does not mention.
+    pub fn load(path: &Path) -> io::Result<Config> {
+        let mut config = Config::new();
+        let mut section = String::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), number + 1, message));
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid(format!("expected key = value, found '{}'", line)))?;
+            let (key, value) = (key.trim(), parse_value(value.trim()).ok_or_else(|| invalid(format!("invalid value '{}'", value.trim())))?);
+            config.set(&section, key, value).map_err(invalid)?;
+        }
+        Ok(config)
+    }
+
+    fn set(&mut self, section: &str, key: &str, value: Value) -> Result<(), String> {
+        let wrong_type = |expected: &str| format!("{}.{} should be {}", section, key, expected);
+        let number = || match value {
+            Value::Number(number) => Ok(number),
+            _ => Err(wrong_type("a number")),
+        };
+        let boolean = || match value {
+            Value::Bool(boolean) => Ok(boolean),
+            _ => Err(wrong_type("true or false")),
+        };
+        let text = || match &value {
+            Value::Text(text) => Ok(text.clone()),
+            _ => Err(wrong_type("a quoted string")),
+        };
+        let size = || number().and_then(|size| if size >= 1.0 && size.fract() == 0.0 { Ok(size as u32) } else { Err(wrong_type("a whole number of pixels")) });
+
+        let v = &mut self.view_state;
+        match (section, key) {
+            ("window", "width") => self.window.width = size()?,
+            ("window", "height") => self.window.height = size()?,
+            ("window", "fullscreen") => self.window.fullscreen = boolean()?,
+            ("window", "vsync") => self.window.vsync = boolean()?,
+            ("window", "fps") => self.window.fps = number()?.max(0.0),
+            ("renderer", "backend") => {
+                self.backend = match text()?.as_str() {
+                    "gpu" => Backend::Gpu,
+                    "cpu" => Backend::Cpu,
+                    "surface" => Backend::Surface,
+                    _ => return Err(wrong_type("\"gpu\", \"cpu\" or \"surface\"")),
+                }
+            },
+            ("renderer", "gpu_backends") => {
+                self.adapter.backends = AdapterOptions::parse_backends(&text()?)
+                    .ok_or_else(|| wrong_type("a list of \"vulkan\", \"gl\", \"metal\" and \"dx12\", or \"all\""))?
+            },
+            ("renderer", "software") => self.adapter.allow_software = boolean()?,
+            ("renderer", "fallback") => self.fallback = boolean()?,
+            ("renderer", "scale") => v.scale = number()?,
+            ("camera", "angle_x") => v.angle_x = number()?,
+            ("camera", "angle_y") => v.angle_y = number()?,
+            ("camera", "angle_z") => v.angle_z = number()?,
+            ("camera", "c_angle_x") => v.c_angle_x = number()?,
+            ("camera", "c_angle_y") => v.c_angle_y = number()?,
+            ("camera", "c_angle_z") => v.c_angle_z = number()?,
+            ("camera", "camera_x") => v.camera_x = number()?,
+            ("camera", "camera_y") => v.camera_y = number()?,
+            ("camera", "camera_z") => v.camera_z = number()?,
+            ("camera", "ref_x") => v.ref_x = number()?,
+            ("camera", "ref_y") => v.ref_y = number()?,
+            ("camera", "ref_z") => v.ref_z = number()?,
+            ("camera", "z_offset") => v.z_offset = number()?,
+            ("lighting", "x") => self.light.x = number()?,
+            ("lighting", "y") => self.light.y = number()?,
+            ("lighting", "z") => self.light.z = number()?,
+            ("lighting", "intensity") => self.light.intensity = number()?,
+            ("lighting", "angle_x") => v.l_angle_x = number()?,
+            ("lighting", "angle_y") => v.l_angle_y = number()?,
+            ("lighting", "angle_z") => v.l_angle_z = number()?,
+            ("input", "bindings") => self.input.bindings = PathBuf::from(text()?),
+            ("input", "acceleration") => self.input.acceleration = number()?,
+            ("input", "damping") => self.input.damping = number()?,
+            ("input", "smoothing") => self.input.smoothing = number()?,
+            ("stream", "budget") => self.stream.budget = Some(number()?).filter(|&budget| budget > 0.0).ok_or_else(|| wrong_type("a positive number of MiB"))?,
+            ("", _) => return Err(format!("'{}' has to be inside a section like [window]", key)),
+            _ => return Err(format!("unknown setting {}.{}", section, key)),
+        }
+        Ok(())
+    }
+
+    /// Command-line options win over the file. The size only sets the window when viewing,
+    /// renders keep the window size as the reference their scale is adjusted from.
+    fn apply(&mut self, options: &Options) {
+        if options.command == Command::View {
+            if let Some(width) = options.width {
+                self.window.width = width;
+            }
+            if let Some(height) = options.height {
+                self.window.height = height;
+            }
+            if let Some(fps) = options.fps {
+                self.window.fps = fps;
+            }
+        }
+        self.window.fullscreen |= options.fullscreen;
+        if let Some(vsync) = options.vsync {
+            self.window.vsync = vsync;
+        }
+        if let Some(backend) = options.backend {
+            self.backend = backend;
+        }
+        if let Some(bindings) = &options.bindings {
+            self.input.bindings = bindings.clone();
+        }
+    }
+
+    /// The settings in effect, in the format `load` reads.
+    pub fn to_toml(&self) -> String {
+        let v = &self.view_state;
+        let mut text = String::new();
+        text += &format!("[window]\nwidth = {}\nheight = {}\nfullscreen = {}\nvsync = {}\nfps = {:?}\n",
+            self.window.width, self.window.height, self.window.fullscreen, self.window.vsync, self.window.fps);
+        text += &format!("\n[renderer]\nbackend = \"{}\"\ngpu_backends = \"{}\"\nsoftware = {}\nfallback = {}\nscale = {:?}\n",
+            self.backend.name(), self.adapter.backend_list(), self.adapter.allow_software, self.fallback, v.scale);
+        text += "\n[camera]\n";
+        for (key, value) in [
+            ("angle_x", v.angle_x), ("angle_y", v.angle_y), ("angle_z", v.angle_z),
+            ("c_angle_x", v.c_angle_x), ("c_angle_y", v.c_angle_y), ("c_angle_z", v.c_angle_z),
+            ("camera_x", v.camera_x), ("camera_y", v.camera_y), ("camera_z", v.camera_z),
+            ("ref_x", v.ref_x), ("ref_y", v.ref_y), ("ref_z", v.ref_z),
+            ("z_offset", v.z_offset),
+        ] {
+            text += &format!("{} = {:?}\n", key, value);
+        }
+        text += "\n[lighting]\n";
+        for (key, value) in [
+            ("x", self.light.x), ("y", self.light.y), ("z", self.light.z), ("intensity", self.light.intensity),
+            ("angle_x", v.l_angle_x), ("angle_y", v.l_angle_y), ("angle_z", v.l_angle_z),
+        ] {
+            text += &format!("{} = {:?}\n", key, value);
+        }
+        text += &format!("\n[input]\nbindings = {:?}\nacceleration = {:?}\ndamping = {:?}\nsmoothing = {:?}\n",
+            self.input.bindings.to_string_lossy(), self.input.acceleration, self.input.damping, self.input.smoothing);
+        text += &format!("\n[stream]\nbudget = {:?}\n", self.stream.budget);
+        text
+    }
+}
+
+enum Value {
+    Number(f32),
+    Bool(bool),
+    Text(String),
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    match text {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        quoted if quoted.len() >= 2 && quoted.starts_with('"') && quoted.ends_with('"') => {
+            let mut value = String::new();
+            let mut chars = quoted[1..quoted.len() - 1].chars();
+            while let Some(c) = chars.next() {
+                value.push(match c {
+                    '\\' => chars.next()?,
+                    '"' => return None,
+                    c => c,
+                });
+            }
+            Some(Value::Text(value))
+        },
+        number => number.replace('_', "").parse().ok().map(Value::Number),
+    }
+}
+
+/// Cuts a `#` comment off the line, unless the `#` is inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printed_config_loads_back_and_unknown_settings_fail() {
+        let mut config = Config::new();
+        config.window.width = 1280;
+        config.backend = Backend::Surface;
+        config.adapter.backends = wgpu::Backends::VULKAN | wgpu::Backends::GL;
+        config.view_state.camera_z = 912.5;
+        config.input.bindings = PathBuf::from("my \"quoted\" bindings.txt");
+        config.stream.budget = 64.5;
+        let path = std::env::temp_dir().join("engine_config_round_trip.toml");
+        fs::write(&path, config.to_toml() + "# trailing comment\n").unwrap();
+
+        let loaded = Config::load(&path).unwrap();
+        assert_eq!(loaded.window.width, 1280);
+        assert_eq!(loaded.backend, Backend::Surface);
+        assert_eq!(loaded.adapter, config.adapter);
+        assert_eq!(loaded.view_state.camera_z, 912.5);
+        assert_eq!(loaded.input.bindings, config.input.bindings);
+        assert_eq!(loaded.stream.budget_bytes(), 64 * 1024 * 1024 + 512 * 1024);
+        assert_eq!(loaded.to_toml(), config.to_toml());
+
+        fs::write(&path, "[window]\nwidht = 1280\n").unwrap();
+        let error = Config::load(&path).err().unwrap();
+        assert!(error.to_string().contains(":2: unknown setting window.widht"));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,317 @@
+use std::fmt;
+use std::path::PathBuf;
+use crate::helpers::bench_helper::{DEFAULT_FRAMES, DEFAULT_PIXEL_COUNTS};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    View,
+    Render,
+    Convert,
+    Info,
+    Parity,
+    Bench,
+}
+
+const COMMANDS: [(Command, &str, &str); 6] = [
+    (Command::View, "view", "opens the scene in a window (default)"),
+    (Command::Render, "render", "renders a camera path offscreen to PNG frames and/or a video encoder"),
+    (Command::Convert, "convert", "splits the model into spatial chunks for streaming"),
+    (Command::Info, "info", "prints the GPU adapter and what the scene files contain"),
+    (Command::Parity, "parity", "renders the same scenes through the cpu and gpu renderers and reports how they differ"),
+    (Command::Bench, "bench", "times a fixed camera path over generated scenes and writes the frame and pass timings as JSON"),
+];
+
+impl Command {
+    pub fn name(self) -> &'static str {
+        COMMANDS.iter().find(|(command, _, _)| *command == self).map(|(_, name, _)| *name).unwrap()
+    }
+
+    fn from_name(name: &str) -> Option<Command> {
+        COMMANDS.iter().find(|(_, command_name, _)| *command_name == name).map(|(command, _, _)| *command)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Backend {
+    Gpu,
+    Cpu,
+    // GPU rendering presented straight to the window's swapchain
+    Surface,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Gpu => "gpu",
+            Backend::Cpu => "cpu",
+            Backend::Surface => "surface",
+        }
+    }
+}
+
+const ALL: &[Command] = &[Command::View, Command::Render, Command::Convert, Command::Info, Command::Parity, Command::Bench];
+const VIEW: &[Command] = &[Command::View];
+const VIEW_RENDER: &[Command] = &[Command::View, Command::Render];
+const FRAMES: &[Command] = &[Command::View, Command::Render, Command::Parity, Command::Bench];
+const BENCH: &[Command] = &[Command::Bench];
+const RENDER: &[Command] = &[Command::Render];
+
+struct OptionDef {
+    name: &'static str,
+    // Placeholder shown in the help, none for flags that take no value
+    value: Option<&'static str>,
+    help: &'static str,
+    commands: &'static [Command],
+}
+
+const OPTIONS: [OptionDef; 19] = [
+    OptionDef { name: "config", value: Some("FILE"), help: "settings file the options below override [default: engine.toml when it exists]", commands: ALL },
+    OptionDef { name: "print-config", value: None, help: "prints the settings in effect, in the config file format, and exits", commands: ALL },
+    OptionDef { name: "scene", value: Some("FILE"), help: "object transforms to load, bookmarks are kept next to it [default: scene.txt]", commands: ALL },
+    OptionDef { name: "backend", value: Some("gpu|cpu|surface"), help: "renderer, surface presents GPU frames without copying them back through SDL and renders offscreen like gpu [config: renderer.backend]", commands: &[Command::View, Command::Render, Command::Bench] },
+    OptionDef { name: "width", value: Some("PIXELS"), help: "window or frame width [config: window.width, the screen's in fullscreen]", commands: FRAMES },
+    OptionDef { name: "height", value: Some("PIXELS"), help: "window or frame height [config: window.height, the screen's in fullscreen]", commands: FRAMES },
+    OptionDef { name: "fullscreen", value: None, help: "opens the window fullscreen [config: window.fullscreen]", commands: VIEW },
+    OptionDef { name: "fps", value: Some("N"), help: "frame rate cap when viewing, 0 for none [config: window.fps]; frames per second of the path when rendering [default: 30]", commands: VIEW_RENDER },
+    OptionDef { name: "vsync", value: Some("on|off"), help: "waits for the display's refresh before presenting [config: window.vsync]", commands: VIEW },
+    OptionDef { name: "output", value: Some("PATH"), help: "where files are written: screenshots when viewing [default: screenshots], PNG frames when rendering, chunks when converting [default: chunks], frames and diff images when comparing backends [default: parity], the report when benchmarking [default: bench.json]", commands: &[Command::View, Command::Render, Command::Convert, Command::Parity, Command::Bench] },
+    OptionDef { name: "encoder", value: Some("COMMAND"), help: "shell command that receives the rendered frames as raw RGB on its standard input", commands: RENDER },
+    OptionDef { name: "path", value: Some("FILE"), help: "camera path to record, play back or render [default: camera_path.txt]", commands: &[Command::View, Command::Render, Command::Info] },
+    OptionDef { name: "view", value: Some("FILE"), help: "starts from the view and light in a screenshot's .json sidecar; also compares the scene from there when comparing backends", commands: FRAMES },
+    OptionDef { name: "stream", value: Some("DIR"), help: "streams the model from the chunks in the directory, converting it there first if needed; rendered frames wait for the chunks around their camera", commands: VIEW_RENDER },
+    OptionDef { name: "bindings", value: Some("FILE"), help: "input bindings and sensitivities [config: input.bindings]", commands: &[Command::View, Command::Info] },
+    OptionDef { name: "pixels", value: Some("N,N,..."), help: "pixel counts of the generated scenes to time [default: 10000,100000,1000000,10000000]", commands: BENCH },
+    OptionDef { name: "frames", value: Some("N"), help: "frames timed per scene, after a short warmup [default: 240]", commands: BENCH },
+    OptionDef { name: "framerate", value: None, help: "prints the frame time and rate", commands: VIEW },
+    OptionDef { name: "help", value: None, help: "prints this help", commands: ALL },
+];
+
+#[derive(Debug)]
+pub enum CliError {
+    UnknownCommand(String),
+    UnknownOption(String),
+    MissingValue(&'static str),
+    InvalidValue { option: &'static str, value: String, expected: &'static str },
+    NotForCommand { option: &'static str, command: Command },
+    Conflict(&'static str),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::UnknownCommand(name) => write!(f, "unknown command '{}', expected view, render, convert, info, parity or bench", name),
+            CliError::UnknownOption(name) => write!(f, "unknown option '{}'", name),
+            CliError::MissingValue(option) => write!(f, "--{} needs a value", option),
+            CliError::InvalidValue { option, value, expected } => write!(f, "invalid value '{}' for --{}, expected {}", value, option, expected),
+            CliError::NotForCommand { option, command } => write!(f, "--{} does not apply to the {} command", option, command.name()),
+            CliError::Conflict(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+pub struct Options {
+    pub command: Command,
+    pub scene: PathBuf,
+    pub config: Option<PathBuf>,
+    pub print_config: bool,
+    pub backend: Option<Backend>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: bool,
+    pub fps: Option<f32>,
+    pub vsync: Option<bool>,
+    pub output: Option<PathBuf>,
+    pub encoder: Option<String>,
+    pub path: PathBuf,
+    pub view: Option<PathBuf>,
+    pub stream: Option<PathBuf>,
+    pub bindings: Option<PathBuf>,
+    pub pixels: Vec<usize>,
+    pub frames: usize,
+    pub framerate: bool,
+    pub help: bool,
+}
+
+impl Options {
+    /// Parses the arguments after the program name: an optional command followed by `--name value`,
+    /// `--name=value` or `--flag` options.
+    pub fn parse(args: &[String]) -> Result<Options, CliError> {
+        let mut args = args.iter().peekable();
+        let command = match args.peek() {
+            Some(arg) if !arg.starts_with('-') => {
+                let command = Command::from_name(arg).ok_or_else(|| CliError::UnknownCommand(arg.to_string()))?;
+                args.next();
+                command
+            },
+            _ => Command::View,
+        };
+
+        let mut options = Options {
+            command,
+            config: None,
+            print_config: false,
+            scene: PathBuf::from("scene.txt"),
+            backend: None,
+            width: None,
+            height: None,
+            fullscreen: false,
+            fps: None,
+            vsync: None,
+            output: None,
+            encoder: None,
+            path: PathBuf::from("camera_path.txt"),
+            view: None,
+            stream: None,
+            bindings: None,
+            pixels: DEFAULT_PIXEL_COUNTS.to_vec(),
+            frames: DEFAULT_FRAMES,
+            framerate: false,
+            help: false,
+        };
+
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.strip_prefix("--").or_else(|| (arg == "-h").then_some("help")) {
+                Some(option) => match option.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (option, None),
+                },
+                None => return Err(CliError::UnknownOption(arg.to_string())),
+            };
+            let definition = OPTIONS.iter().find(|definition| definition.name == name).ok_or_else(|| CliError::UnknownOption(arg.to_string()))?;
+            if !definition.commands.contains(&command) {
+                return Err(CliError::NotForCommand { option: definition.name, command });
+            }
+
+            let value = match (definition.value, inline_value) {
+                (None, Some(value)) => return Err(CliError::InvalidValue { option: definition.name, value, expected: "no value" }),
+                (None, None) => String::new(),
+                (Some(_), Some(value)) => value,
+                (Some(_), None) => args.next().cloned().ok_or(CliError::MissingValue(definition.name))?,
+            };
+            options.set(definition.name, value)?;
+        }
+
+        if options.help || options.print_config {
+            return Ok(options);
+        }
+        if options.command == Command::Render && options.output.is_none() && options.encoder.is_none() {
+            return Err(CliError::Conflict("render needs --output, --encoder or both"));
+        }
+        if options.command == Command::Render && options.fps == Some(0.0) {
+            return Err(CliError::InvalidValue { option: "fps", value: String::from("0"), expected: "a frame rate above 0" });
+        }
+        Ok(options)
+    }
+
+    fn set(&mut self, name: &'static str, value: String) -> Result<(), CliError> {
+        let invalid = |expected| CliError::InvalidValue { option: name, value: value.clone(), expected };
+        match name {
+            "config" => self.config = Some(PathBuf::from(&value)),
+            "print-config" => self.print_config = true,
+            "scene" => self.scene = PathBuf::from(&value),
+            "backend" => {
+                self.backend = match value.as_str() {
+                    "gpu" => Some(Backend::Gpu),
+                    "cpu" => Some(Backend::Cpu),
+                    "surface" => Some(Backend::Surface),
+                    _ => return Err(invalid("gpu, cpu or surface")),
+                }
+            },
+            "width" => self.width = Some(value.parse().ok().filter(|&width| width > 0).ok_or_else(|| invalid("a width in pixels"))?),
+            "height" => self.height = Some(value.parse().ok().filter(|&height| height > 0).ok_or_else(|| invalid("a height in pixels"))?),
+            "fullscreen" => self.fullscreen = true,
+            "fps" => self.fps = Some(value.parse().ok().filter(|fps: &f32| fps.is_finite() && *fps >= 0.0).ok_or_else(|| invalid("a frame rate"))?),
+            "vsync" => {
+                self.vsync = match value.as_str() {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => return Err(invalid("on or off")),
+                }
+            },
+            "output" => self.output = Some(PathBuf::from(&value)),
+            "encoder" => self.encoder = Some(value),
+            "path" => self.path = PathBuf::from(&value),
+            "view" => self.view = Some(PathBuf::from(&value)),
+            "stream" => self.stream = Some(PathBuf::from(&value)),
+            "bindings" => self.bindings = Some(PathBuf::from(&value)),
+            "pixels" => {
+                self.pixels = value
+                    .split(',')
+                    .map(|count| count.trim().replace('_', "").parse().ok().filter(|&count: &usize| count > 0))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| invalid("pixel counts separated by commas"))?
+            },
+            "frames" => self.frames = value.parse().ok().filter(|&frames| frames > 0).ok_or_else(|| invalid("a number of frames"))?,
+            "framerate" => self.framerate = true,
+            "help" => self.help = true,
+            _ => unreachable!("option {} has no setter", name),
+        }
+        Ok(())
+    }
+}
+
+/// Help for a command, built from the command and option tables.
+pub fn help(program: &str, command: Command) -> String {
+    let mut text = format!("Usage: {} [COMMAND] [OPTIONS]\n\nCommands:\n", program);
+    for (_, name, description) in COMMANDS {
+        text += &format!("  {:<10}{}\n", name, description);
+    }
+
+    text += &format!("\nOptions for {}:\n", command.name());
+    let options: Vec<(String, &str)> = OPTIONS
+        .iter()
+        .filter(|definition| definition.commands.contains(&command))
+        .map(|definition| match definition.value {
+            Some(value) => (format!("--{} <{}>", definition.name, value), definition.help),
+            None => (format!("--{}", definition.name), definition.help),
+        })
+        .collect();
+    let width = options.iter().map(|(usage, _)| usage.len()).max().unwrap_or(0);
+    for (usage, help) in options {
+        text += &format!("  {:<width$}  {}\n", usage, help, width = width);
+    }
+    text += &format!("\nRun '{} <COMMAND> --help' for the options of another command.\n", program);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        Options::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn parses_commands_and_both_value_forms() {
+        let options = parse(&["render", "--width", "1920", "--height=1080", "--output", "frames", "--backend", "cpu"]).unwrap();
+        assert_eq!(options.command, Command::Render);
+        assert_eq!((options.width, options.height), (Some(1920), Some(1080)));
+        assert_eq!(options.output, Some(PathBuf::from("frames")));
+        assert_eq!(options.backend, Some(Backend::Cpu));
+
+        let options = parse(&["--backend", "cpu", "--fullscreen"]).unwrap();
+        assert_eq!(options.command, Command::View);
+        assert_eq!(options.backend, Some(Backend::Cpu));
+        assert!(options.fullscreen);
+
+        let options = parse(&["render", "--output", "frames", "--stream", "chunks"]).unwrap();
+        assert_eq!(options.stream, Some(PathBuf::from("chunks")));
+
+        let options = parse(&["bench", "--pixels", "10_000,2000000", "--frames=60"]).unwrap();
+        assert_eq!(options.pixels, vec![10_000, 2_000_000]);
+        assert_eq!(options.frames, 60);
+    }
+
+    #[test]
+    fn rejects_bad_input_with_the_option_named() {
+        assert!(matches!(parse(&["draw"]), Err(CliError::UnknownCommand(_))));
+        assert!(matches!(parse(&["--colour"]), Err(CliError::UnknownOption(_))));
+        assert!(matches!(parse(&["--width"]), Err(CliError::MissingValue("width"))));
+        assert!(matches!(parse(&["--width", "wide"]), Err(CliError::InvalidValue { option: "width", .. })));
+        assert!(matches!(parse(&["info", "--fullscreen"]), Err(CliError::NotForCommand { option: "fullscreen", command: Command::Info })));
+        assert!(matches!(parse(&["render"]), Err(CliError::Conflict(_))));
+    }
+}
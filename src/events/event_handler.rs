@@ -1,143 +1,376 @@
-use sdl2::{event::{Event, WindowEvent}, keyboard::Keycode, mouse::MouseButton, EventPump};
-use crate::types::{event_callback::EventCallback, light::Light, view_state::ViewState};
+use sdl2::{controller::GameController, event::{Event, WindowEvent}, keyboard::Mod, EventPump, GameControllerSubsystem};
+use crate::scene::{editor::Editor, gizmo::{Axis, GizmoMode}};
+use crate::types::{camera_controller::{CameraController, CameraInput}, event_callback::EventCallback, light::Light, view_state::ViewState};
+use super::input_map::{Action, Input, InputMap, Modifiers};
+
+// Stick positions closer to the center than this are treated as centered
+const STICK_DEAD_ZONE: f32 = 0.2;
 
 pub struct EventHandler {
     event_pump: EventPump,
+    input_map: InputMap,
+    controller_subsystem: Option<GameControllerSubsystem>,
+    controllers: Vec<GameController>,
     last_x: i32,
     last_y: i32,
-    drag: bool,
-    r_drag: bool,
-    m_drag: bool,
-    move_forward: bool,
-    move_backward: bool,
-    move_left: bool,
-    move_right: bool,
-    r_move_forward: bool,
-    r_move_backward: bool,
-    r_move_left: bool,
-    r_move_right: bool,
-    ctrl: bool,
+    click: bool,
+    modifiers: Modifiers,
+    // Actions held down together with the input that started them and the instance id of the controller
+    // it is on, so releasing it ends them even when the modifiers changed in between, and how far they
+    // are held from 0 to 1
+    held: Vec<(Input, Option<u32>, Action, f32)>,
+    // Velocities in units per second, to the right, up and backward for the camera and along x and z for ref
+    camera_velocity: (f32, f32, f32),
+    ref_velocity: (f32, f32),
+    // Camera turns gathered over the frame
+    look: (f32, f32),
+    pointer: (f32, f32),
+    // Mouse rotation not applied yet while smoothing, in pixels dragged
+    pending_rotation: Vec<(Action, f32, f32)>,
 }
 
 impl EventHandler {
-    pub fn new(event_pump: EventPump) -> EventHandler {
+    /// Controllers are opened as SDL reports them, including the ones connected at startup.
+    pub fn new(event_pump: EventPump, input_map: InputMap, controller_subsystem: Option<GameControllerSubsystem>) -> EventHandler {
         EventHandler {
             event_pump,
+            input_map,
+            controller_subsystem,
+            controllers: Vec::new(),
             last_x: 0,
             last_y: 0,
-            drag: false,
-            r_drag: false,
-            m_drag: false,
-            move_forward: false,
-            move_backward: false,
-            move_left: false,
-            move_right: false,
-            r_move_forward: false,
-            r_move_backward: false,
-            r_move_left: false,
-            r_move_right: false,
-            ctrl: false
+            click: false,
+            modifiers: Modifiers::default(),
+            held: Vec::new(),
+            camera_velocity: (0.0, 0.0, 0.0),
+            ref_velocity: (0.0, 0.0),
+            look: (0.0, 0.0),
+            pointer: (0.0, 0.0),
+            pending_rotation: Vec::new(),
         }
     }
 
-    pub fn handle_events(&mut self, view_state: &mut ViewState, light: &mut Light) -> Option<EventCallback> {
-        for event in self.event_pump.poll_iter() {
+    /// Handles the pending events and advances movement by `delta_time` seconds, moving the view's
+    /// camera through the given controller. Returns what the events asked of the application, in order.
+    pub fn handle_events(
+        &mut self,
+        view_state: &mut ViewState,
+        light: &mut Light,
+        editor: &mut Editor,
+        camera: &mut dyn CameraController,
+        delta_time: f32
+    ) -> Vec<EventCallback> {
+        let mut callbacks = Vec::new();
+        while let Some(event) = self.event_pump.poll_event() {
             match event {
                 Event::Window {
                     win_event: WindowEvent::SizeChanged(width, height),
                     ..
-                } => return Some(EventCallback::Resized(width as u32, height as u32)),
-                Event::Quit { .. }
-                | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return Some(EventCallback::Quit),
-                Event::KeyDown { keycode: Some(key), .. } | Event::KeyUp { keycode: Some(key), .. } => {
-                    let pressed = matches!(event, Event::KeyDown { .. });
-                    match key {
-                        Keycode::W => self.move_forward = pressed,
-                        Keycode::S => self.move_backward = pressed,
-                        Keycode::A => self.move_left = pressed,
-                        Keycode::D => self.move_right = pressed,
-                        Keycode::Up => self.r_move_forward = pressed,
-                        Keycode::Down => self.r_move_backward = pressed,
-                        Keycode::Left => self.r_move_left = pressed,
-                        Keycode::Right => self.r_move_right = pressed,
-                        Keycode::LCtrl => self.ctrl = pressed,
-                        _ => {}
+                } => callbacks.push(EventCallback::Resized(width as u32, height as u32)),
+                Event::Quit { .. } => callbacks.push(EventCallback::Quit),
+                Event::KeyDown { keycode: Some(key), keymod, repeat, .. } => {
+                    self.set_modifiers(keymod);
+                    if !repeat {
+                        callbacks.extend(self.press(Input::Key(key), None, editor));
                     }
                 },
-                Event::MouseButtonDown { mouse_btn, x, y, .. } => match mouse_btn {
-                    MouseButton::Left => {
-                        self.drag = true;
-                        self.last_x = x;
-                        self.last_y = y;
-                    },
-                    MouseButton::Right => {
-                        self.r_drag = true;
-                        self.last_x = x;
-                        self.last_y = y;
-                    },
-                    MouseButton::Middle => {
-                        self.m_drag = true;
-                        self.last_x = x;
-                        self.last_y = y;
-                    },
-                    _ => {}
+                Event::KeyUp { keycode: Some(key), keymod, .. } => {
+                    self.set_modifiers(keymod);
+                    self.release(Input::Key(key), None);
+                },
+                Event::MouseButtonDown { mouse_btn, x, y, .. } => {
+                    self.last_x = x;
+                    self.last_y = y;
+                    callbacks.extend(self.press(Input::Mouse(mouse_btn), None, editor));
                 },
-                Event::MouseButtonUp { mouse_btn, .. } => match mouse_btn {
-                    MouseButton::Left => self.drag = false,
-                    MouseButton::Right => self.r_drag = false,
-                    MouseButton::Middle => self.m_drag = false,
-                    _ => {}
+                Event::MouseButtonUp { mouse_btn, x, y, .. } => {
+                    let released = self.release(Input::Mouse(mouse_btn), None);
+                    if released.contains(&Action::RotateScene) {
+                        editor.end_drag();
+                        // A press and release without dragging in between selects what is under the cursor
+                        if self.click {
+                            self.click = false;
+                            callbacks.push(EventCallback::Pick(x, y));
+                        }
+                    }
                 },
                 Event::MouseMotion { x, y, .. } => {
-                    if self.drag {
-                        let dx = x - self.last_x;
-                        let dy = y - self.last_y;
-                        view_state.angle_x = (view_state.angle_x + (dy as f32 * 0.01)).rem_euclid(2.0 * std::f32::consts::PI);
-                        view_state.angle_y = (view_state.angle_y + (dx as f32 * 0.01)).rem_euclid(2.0 * std::f32::consts::PI);
-                        self.last_x = x;
-                        self.last_y = y;
+                    let dx = x - self.last_x;
+                    let dy = y - self.last_y;
+                    self.last_x = x;
+                    self.last_y = y;
+                    if dx != 0 || dy != 0 {
+                        self.click = false;
                     }
-                    if self.r_drag {
-                        let dx = x - self.last_x;
-                        let dy = y - self.last_y;
-                        light.x += dx as f32 * 0.5;
-                        light.y -= dy as f32 * 0.5;
-                        self.last_x = x;
-                        self.last_y = y;
+                    self.drag(dx as f32, dy as f32, view_state, light, editor);
+                },
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Some(controller) = self.controller_subsystem.as_ref().and_then(|subsystem| subsystem.open(which).ok()) {
+                        let name = controller.name();
+                        self.controllers.push(controller);
+                        callbacks.push(EventCallback::ControllerConnected(name));
+                    }
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    // Whatever the controller held would otherwise stay held, other controllers keep theirs
+                    self.held.retain(|&(_, controller, _, _)| controller != Some(which));
+                    if let Some(position) = self.controllers.iter().position(|controller| controller.instance_id() == which) {
+                        callbacks.push(EventCallback::ControllerDisconnected(self.controllers.remove(position).name()));
                     }
-                    if self.m_drag {
-                        let dx = x - self.last_x;
-                        let dy = y - self.last_y;
-                        if self.ctrl {
-                            view_state.l_angle_x = (view_state.l_angle_x - (dy as f32 * 0.01)).rem_euclid(2.0 * std::f32::consts::PI);
-                            view_state.l_angle_y = (view_state.l_angle_y - (dx as f32 * 0.01)).rem_euclid(2.0 * std::f32::consts::PI);
-                        } else {
-                            view_state.c_angle_x = (view_state.c_angle_x - (dy as f32 * 0.01)).rem_euclid(2.0 * std::f32::consts::PI);
-                            view_state.c_angle_y = (view_state.c_angle_y - (dx as f32 * 0.01)).rem_euclid(2.0 * std::f32::consts::PI);
+                },
+                Event::ControllerButtonDown { which, button, .. } => {
+                    callbacks.extend(self.press(Input::PadButton(button), Some(which), editor));
+                },
+                Event::ControllerButtonUp { which, button, .. } => {
+                    self.release(Input::PadButton(button), Some(which));
+                },
+                Event::ControllerAxisMotion { which, axis, value, .. } => {
+                    // Each direction of an axis is an input of its own, held as far as the stick is pushed
+                    let value = (value as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+                    for (positive, amount) in [(true, value), (false, -value)] {
+                        let input = Input::PadAxis(axis, positive);
+                        self.release(input, Some(which));
+                        if amount > STICK_DEAD_ZONE && let Some(action) = self.input_map.action(input, self.modifiers) {
+                            self.held.push((input, Some(which), action, (amount - STICK_DEAD_ZONE) / (1.0 - STICK_DEAD_ZONE)));
                         }
-                        self.last_x = x;
-                        self.last_y = y;
                     }
                 },
                 Event::MouseWheel { y, .. } => {
-                    light.intensity += y as f32 * 0.5;
-                    if light.intensity < 0.0 {
-                        light.intensity = 0.0;
-                    }
+                    callbacks.extend(self.scroll(y as f32, view_state, light, editor));
                 },
                 _ => {}
             }
         }
 
-        if self.move_forward { view_state.camera_z -= 2.0; }
-        if self.move_backward { view_state.camera_z += 2.0; }
-        if self.move_left { view_state.camera_x += 2.0; }
-        if self.move_right { view_state.camera_x -= 2.0; }
-        if self.r_move_forward { view_state.ref_z -= 2.0; }
-        if self.r_move_backward { view_state.ref_z += 2.0; }
-        if self.r_move_left { view_state.ref_x -= 2.0; }
-        if self.r_move_right { view_state.ref_x += 2.0; }
+        let mut camera_target = (0.0, 0.0, 0.0);
+        let mut ref_target = (0.0, 0.0);
+        for &(_, _, action, amount) in &self.held {
+            let speed = self.input_map.sensitivity(action) * amount;
+            match action {
+                Action::CameraForward => camera_target.2 -= speed,
+                Action::CameraBackward => camera_target.2 += speed,
+                Action::CameraLeft => camera_target.0 -= speed,
+                Action::CameraRight => camera_target.0 += speed,
+                Action::CameraUp => camera_target.1 += speed,
+                Action::CameraDown => camera_target.1 -= speed,
+                Action::RefForward => ref_target.1 -= speed,
+                Action::RefBackward => ref_target.1 += speed,
+                Action::RefLeft => ref_target.0 -= speed,
+                Action::RefRight => ref_target.0 += speed,
+                Action::OrbitLeft => self.look.0 += speed * delta_time,
+                Action::OrbitRight => self.look.0 -= speed * delta_time,
+                Action::OrbitUp => self.look.1 += speed * delta_time,
+                Action::OrbitDown => self.look.1 -= speed * delta_time,
+                Action::LightBrighter => light.intensity += speed * delta_time,
+                Action::LightDimmer => light.intensity = (light.intensity - speed * delta_time).max(0.0),
+                _ => {}
+            }
+        }
 
+        self.camera_velocity = (
+            self.accelerate(self.camera_velocity.0, camera_target.0, delta_time),
+            self.accelerate(self.camera_velocity.1, camera_target.1, delta_time),
+            self.accelerate(self.camera_velocity.2, camera_target.2, delta_time),
+        );
+        self.ref_velocity = (
+            self.accelerate(self.ref_velocity.0, ref_target.0, delta_time),
+            self.accelerate(self.ref_velocity.1, ref_target.1, delta_time),
+        );
+        view_state.ref_x += self.ref_velocity.0 * delta_time;
+        view_state.ref_z += self.ref_velocity.1 * delta_time;
+
+        // Eases the dragged rotation in, the part applied each frame depending only on the elapsed time
+        let portion = if self.input_map.smoothing > 0.0 {
+            1.0 - (-delta_time / self.input_map.smoothing).exp()
+        } else {
+            1.0
+        };
+        for (action, dx, dy) in std::mem::take(&mut self.pending_rotation) {
+            self.rotate(action, dx * portion, dy * portion, view_state);
+            if (dx * (1.0 - portion)).abs() > 0.01 || (dy * (1.0 - portion)).abs() > 0.01 {
+                self.pending_rotation.push((action, dx * (1.0 - portion), dy * (1.0 - portion)));
+            }
+        }
+
+        let input = CameraInput {
+            movement: self.camera_velocity,
+            look: std::mem::take(&mut self.look),
+            pointer: std::mem::take(&mut self.pointer),
+        };
+        camera.update(&input, delta_time).apply(view_state);
+
+        callbacks
+    }
+
+    /// Speeds a velocity up towards the target velocity, or lets it decay when nothing is held.
+    fn accelerate(&self, velocity: f32, target: f32, delta_time: f32) -> f32 {
+        if target == 0.0 {
+            velocity * (-self.input_map.damping * delta_time).exp()
+        } else {
+            let step = self.input_map.acceleration * delta_time;
+            velocity + (target - velocity).clamp(-step, step)
+        }
+    }
+
+    fn set_modifiers(&mut self, keymod: Mod) {
+        self.modifiers = Modifiers::from_mod(keymod);
+    }
+
+    fn is_held(&self, action: Action) -> bool {
+        self.held.iter().any(|&(_, _, held_action, _)| held_action == action)
+    }
+
+    /// Runs one-shot actions right away and keeps the others held until their input is released.
+    fn press(&mut self, input: Input, controller: Option<u32>, editor: &mut Editor) -> Option<EventCallback> {
+        let action = self.input_map.action(input, self.modifiers)?;
+        if let Some(callback) = run_once(action, editor) {
+            return callback;
+        }
+        if action == Action::RotateScene {
+            self.click = true;
+        }
+        self.held.push((input, controller, action, 1.0));
         None
     }
+
+    /// Runs the wheel's action once per step, a step moving as far as its sensitivity. Movement gets
+    /// a push that glides out like a released key, rotations turn like a vertical drag.
+    fn scroll(&mut self, steps: f32, view_state: &mut ViewState, light: &mut Light, editor: &mut Editor) -> Option<EventCallback> {
+        let action = self.input_map.action(Input::Wheel, self.modifiers)?;
+        if let Some(callback) = run_once(action, editor) {
+            return callback;
+        }
+        let step = steps * self.input_map.sensitivity(action);
+        match action {
+            Action::CameraForward => self.camera_velocity.2 -= step,
+            Action::CameraBackward => self.camera_velocity.2 += step,
+            Action::CameraLeft => self.camera_velocity.0 -= step,
+            Action::CameraRight => self.camera_velocity.0 += step,
+            Action::CameraUp => self.camera_velocity.1 += step,
+            Action::CameraDown => self.camera_velocity.1 -= step,
+            Action::RefForward => self.ref_velocity.1 -= step,
+            Action::RefBackward => self.ref_velocity.1 += step,
+            Action::RefLeft => self.ref_velocity.0 -= step,
+            Action::RefRight => self.ref_velocity.0 += step,
+            Action::OrbitLeft => self.look.0 += step,
+            Action::OrbitRight => self.look.0 -= step,
+            Action::OrbitUp => self.look.1 += step,
+            Action::OrbitDown => self.look.1 -= step,
+            Action::RotateScene | Action::RotateCamera | Action::RotateLight => self.rotate(action, 0.0, steps, view_state),
+            Action::MoveLight => light.y += step,
+            Action::LightIntensity | Action::LightBrighter => light.intensity = (light.intensity + step).max(0.0),
+            Action::LightDimmer => light.intensity = (light.intensity - step).max(0.0),
+            // Snapping only applies while its input is held
+            _ => {}
+        }
+        None
+    }
+
+    fn release(&mut self, input: Input, controller: Option<u32>) -> Vec<Action> {
+        let mut released = Vec::new();
+        self.held.retain(|&(held_input, held_controller, action, _)| {
+            let releases = held_input == input && held_controller == controller;
+            if releases {
+                released.push(action);
+            }
+            !releases
+        });
+        released
+    }
+
+    fn drag(&mut self, dx: f32, dy: f32, view_state: &mut ViewState, light: &mut Light, editor: &mut Editor) {
+        let snap = self.is_held(Action::Snap);
+        if !self.is_held(Action::RotateCamera) {
+            let speed = self.input_map.sensitivity(Action::RotateCamera);
+            self.pointer.0 -= dx * speed;
+            self.pointer.1 -= dy * speed;
+        }
+        for &(_, _, action, _) in &self.held {
+            let speed = self.input_map.sensitivity(action);
+            match action {
+                // With an object selected dragging edits it, otherwise it rotates the scene
+                Action::RotateScene if editor.selected_id().is_some() => {
+                    editor.drag(dx as i32, dy as i32, snap, view_state);
+                },
+                Action::RotateScene | Action::RotateCamera | Action::RotateLight => {
+                    match self.pending_rotation.iter_mut().find(|(pending, _, _)| *pending == action) {
+                        Some((_, pending_dx, pending_dy)) => {
+                            *pending_dx += dx;
+                            *pending_dy += dy;
+                        },
+                        None => self.pending_rotation.push((action, dx, dy)),
+                    }
+                },
+                Action::MoveLight => {
+                    light.x += dx * speed;
+                    light.y -= dy * speed;
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn rotate(&mut self, action: Action, dx: f32, dy: f32, view_state: &mut ViewState) {
+        let speed = self.input_map.sensitivity(action);
+        match action {
+            Action::RotateScene => {
+                view_state.angle_x = (view_state.angle_x + dy * speed).rem_euclid(2.0 * std::f32::consts::PI);
+                view_state.angle_y = (view_state.angle_y + dx * speed).rem_euclid(2.0 * std::f32::consts::PI);
+            },
+            Action::RotateCamera => {
+                self.look.0 -= dx * speed;
+                self.look.1 -= dy * speed;
+            },
+            Action::RotateLight => {
+                view_state.l_angle_x = (view_state.l_angle_x - dy * speed).rem_euclid(2.0 * std::f32::consts::PI);
+                view_state.l_angle_y = (view_state.l_angle_y - dx * speed).rem_euclid(2.0 * std::f32::consts::PI);
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Runs an action that happens once per press, returning its callback, or None for actions that last while held.
+fn run_once(action: Action, editor: &mut Editor) -> Option<Option<EventCallback>> {
+    let callback = match action {
+        Action::Quit => Some(EventCallback::Quit),
+        Action::SaveScene => Some(EventCallback::SaveScene),
+        Action::CycleCamera => Some(EventCallback::CycleCamera),
+        Action::RecordPath => Some(EventCallback::RecordPath),
+        Action::PlayPath => Some(EventCallback::PlayPath),
+        Action::Screenshot => Some(EventCallback::Screenshot),
+        Action::SwitchBackend => Some(EventCallback::SwitchBackend),
+        Action::ToggleOverlay => Some(EventCallback::ToggleOverlay),
+        Action::ToggleShadows => Some(EventCallback::ToggleShadows),
+        Action::ToggleLighting => Some(EventCallback::ToggleLighting),
+        Action::CycleDebugView => Some(EventCallback::CycleDebugView),
+        Action::Bookmark(slot) => Some(EventCallback::Bookmark(slot as usize)),
+        Action::SaveBookmark(slot) => Some(EventCallback::SaveBookmark(slot as usize)),
+        Action::GizmoTranslate => {
+            editor.set_mode(GizmoMode::Translate);
+            None
+        },
+        Action::GizmoRotate => {
+            editor.set_mode(GizmoMode::Rotate);
+            None
+        },
+        Action::GizmoScale => {
+            editor.set_mode(GizmoMode::Scale);
+            None
+        },
+        Action::AxisX => {
+            editor.toggle_axis(Axis::X);
+            None
+        },
+        Action::AxisY => {
+            editor.toggle_axis(Axis::Y);
+            None
+        },
+        Action::AxisZ => {
+            editor.toggle_axis(Axis::Z);
+            None
+        },
+        _ => return None,
+    };
+    Some(callback)
 }
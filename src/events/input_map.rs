@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use sdl2::{controller, keyboard::{Keycode, Mod}, mouse::MouseButton};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    CameraForward,
+    CameraBackward,
+    CameraLeft,
+    CameraRight,
+    CameraUp,
+    CameraDown,
+    CycleCamera,
+    RefForward,
+    RefBackward,
+    RefLeft,
+    RefRight,
+    RotateScene,
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    RotateCamera,
+    RotateLight,
+    MoveLight,
+    LightIntensity,
+    LightBrighter,
+    LightDimmer,
+    GizmoTranslate,
+    GizmoRotate,
+    GizmoScale,
+    AxisX,
+    AxisY,
+    AxisZ,
+    Snap,
+    SaveScene,
+    RecordPath,
+    PlayPath,
+    Screenshot,
+    SwitchBackend,
+    ToggleOverlay,
+    ToggleShadows,
+    ToggleLighting,
+    CycleDebugView,
+    // Recalls or stores the numbered view bookmark
+    Bookmark(u8),
+    SaveBookmark(u8),
+    Quit,
+}
+
+const ACTION_NAMES: [(Action, &str); 59] = [
+    (Action::CameraForward, "camera_forward"),
+    (Action::CameraBackward, "camera_backward"),
+    (Action::CameraLeft, "camera_left"),
+    (Action::CameraRight, "camera_right"),
+    (Action::CameraUp, "camera_up"),
+    (Action::CameraDown, "camera_down"),
+    (Action::CycleCamera, "cycle_camera"),
+    (Action::RefForward, "ref_forward"),
+    (Action::RefBackward, "ref_backward"),
+    (Action::RefLeft, "ref_left"),
+    (Action::RefRight, "ref_right"),
+    (Action::RotateScene, "rotate_scene"),
+    (Action::OrbitLeft, "orbit_left"),
+    (Action::OrbitRight, "orbit_right"),
+    (Action::OrbitUp, "orbit_up"),
+    (Action::OrbitDown, "orbit_down"),
+    (Action::RotateCamera, "rotate_camera"),
+    (Action::RotateLight, "rotate_light"),
+    (Action::MoveLight, "move_light"),
+    (Action::LightIntensity, "light_intensity"),
+    (Action::LightBrighter, "light_brighter"),
+    (Action::LightDimmer, "light_dimmer"),
+    (Action::GizmoTranslate, "gizmo_translate"),
+    (Action::GizmoRotate, "gizmo_rotate"),
+    (Action::GizmoScale, "gizmo_scale"),
+    (Action::AxisX, "axis_x"),
+    (Action::AxisY, "axis_y"),
+    (Action::AxisZ, "axis_z"),
+    (Action::Snap, "snap"),
+    (Action::SaveScene, "save_scene"),
+    (Action::RecordPath, "record_path"),
+    (Action::PlayPath, "play_path"),
+    (Action::Screenshot, "screenshot"),
+    (Action::SwitchBackend, "switch_backend"),
+    (Action::ToggleOverlay, "toggle_overlay"),
+    (Action::ToggleShadows, "toggle_shadows"),
+    (Action::ToggleLighting, "toggle_lighting"),
+    (Action::CycleDebugView, "cycle_debug_view"),
+    (Action::Bookmark(0), "bookmark_0"),
+    (Action::Bookmark(1), "bookmark_1"),
+    (Action::Bookmark(2), "bookmark_2"),
+    (Action::Bookmark(3), "bookmark_3"),
+    (Action::Bookmark(4), "bookmark_4"),
+    (Action::Bookmark(5), "bookmark_5"),
+    (Action::Bookmark(6), "bookmark_6"),
+    (Action::Bookmark(7), "bookmark_7"),
+    (Action::Bookmark(8), "bookmark_8"),
+    (Action::Bookmark(9), "bookmark_9"),
+    (Action::SaveBookmark(0), "save_bookmark_0"),
+    (Action::SaveBookmark(1), "save_bookmark_1"),
+    (Action::SaveBookmark(2), "save_bookmark_2"),
+    (Action::SaveBookmark(3), "save_bookmark_3"),
+    (Action::SaveBookmark(4), "save_bookmark_4"),
+    (Action::SaveBookmark(5), "save_bookmark_5"),
+    (Action::SaveBookmark(6), "save_bookmark_6"),
+    (Action::SaveBookmark(7), "save_bookmark_7"),
+    (Action::SaveBookmark(8), "save_bookmark_8"),
+    (Action::SaveBookmark(9), "save_bookmark_9"),
+    (Action::Quit, "quit"),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTION_NAMES.iter().find(|(_, action_name)| *action_name == name).map(|(action, _)| *action)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+    Key(Keycode),
+    Mouse(MouseButton),
+    Wheel,
+    PadButton(controller::Button),
+    // One direction of a stick or trigger, true for positive values
+    PadAxis(controller::Axis, bool),
+}
+
+/// Modifier keys, without telling the left and right ones apart.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    pub fn from_mod(keymod: Mod) -> Modifiers {
+        Modifiers {
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        }
+    }
+
+    fn contains(self, other: Modifiers) -> bool {
+        (self.ctrl || !other.ctrl) && (self.shift || !other.shift) && (self.alt || !other.alt)
+    }
+
+    fn count(self) -> usize {
+        self.ctrl as usize + self.shift as usize + self.alt as usize
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Binding {
+    pub input: Input,
+    pub modifiers: Modifiers,
+}
+
+impl Binding {
+    /// Parses bindings like `W`, `Left Shift`, `Ctrl+Mouse Middle`, `Wheel`, `Pad a` or `Pad -lefty`.
+    /// Keys, controller buttons and controller axes use their SDL names, axes with the direction in front.
+    pub fn parse(text: &str) -> Option<Binding> {
+        let mut modifiers = Modifiers::default();
+        let mut rest = text.trim();
+        loop {
+            if let Some(stripped) = rest.strip_prefix("Ctrl+") {
+                modifiers.ctrl = true;
+                rest = stripped;
+            } else if let Some(stripped) = rest.strip_prefix("Shift+") {
+                modifiers.shift = true;
+                rest = stripped;
+            } else if let Some(stripped) = rest.strip_prefix("Alt+") {
+                modifiers.alt = true;
+                rest = stripped;
+            } else {
+                break;
+            }
+        }
+
+        let input = match rest {
+            "Wheel" => Input::Wheel,
+            "Mouse Left" => Input::Mouse(MouseButton::Left),
+            "Mouse Middle" => Input::Mouse(MouseButton::Middle),
+            "Mouse Right" => Input::Mouse(MouseButton::Right),
+            "Mouse X1" => Input::Mouse(MouseButton::X1),
+            "Mouse X2" => Input::Mouse(MouseButton::X2),
+            pad if pad.starts_with("Pad ") => {
+                let name = &pad["Pad ".len()..];
+                match name.split_at_checked(1) {
+                    Some(("+", axis)) => Input::PadAxis(controller::Axis::from_string(axis)?, true),
+                    Some(("-", axis)) => Input::PadAxis(controller::Axis::from_string(axis)?, false),
+                    _ => Input::PadButton(controller::Button::from_string(name)?),
+                }
+            },
+            key => Input::Key(Keycode::from_name(key)?),
+        };
+        Some(Binding { input, modifiers })
+    }
+}
+
+/// Maps inputs to actions. Defaults can be rebound from a file with lines like:
+///
+/// ```text
+/// # Replaces the default bindings of the action, repeat the line to bind more inputs
+/// bind camera_forward Up
+/// bind rotate_light Ctrl+Mouse Middle
+/// # Removes all bindings of the action
+/// unbind ref_forward
+/// # Scales how far the action moves or rotates per second held, pixel dragged or wheel step
+/// sensitivity rotate_scene 0.02
+/// # How fast movement speeds up and slows down, in units per second squared and per second
+/// acceleration 400
+/// damping 8
+/// # Seconds over which mouse rotation is eased in, 0 applies it right away
+/// smoothing 0.05
+/// ```
+pub struct InputMap {
+    bindings: Vec<(Action, Binding)>,
+    sensitivity: HashMap<Action, f32>,
+    pub acceleration: f32,
+    pub damping: f32,
+    pub smoothing: f32,
+}
+
+impl InputMap {
+    pub fn new() -> InputMap {
+        let mut input_map = InputMap {
+            bindings: Vec::new(),
+            sensitivity: HashMap::new(),
+            acceleration: 400.0,
+            damping: 8.0,
+            smoothing: 0.0,
+        };
+
+        let defaults: [(Action, &[&str], f32); 59] = [
+            (Action::CameraForward, &["W", "Pad -lefty"], 50.0),
+            (Action::CameraBackward, &["S", "Pad +lefty"], 50.0),
+            (Action::CameraLeft, &["A", "Pad -leftx"], 50.0),
+            (Action::CameraRight, &["D", "Pad +leftx"], 50.0),
+            (Action::CameraUp, &["Space", "Pad rightshoulder"], 50.0),
+            (Action::CameraDown, &["C", "Pad leftshoulder"], 50.0),
+            (Action::CycleCamera, &["Tab", "Pad y"], 1.0),
+            (Action::RefForward, &["Up", "Pad dpup"], 50.0),
+            (Action::RefBackward, &["Down", "Pad dpdown"], 50.0),
+            (Action::RefLeft, &["Left", "Pad dpleft"], 50.0),
+            (Action::RefRight, &["Right", "Pad dpright"], 50.0),
+            (Action::RotateScene, &["Mouse Left"], 0.01),
+            (Action::OrbitLeft, &["Pad -rightx"], 0.7),
+            (Action::OrbitRight, &["Pad +rightx"], 0.7),
+            (Action::OrbitUp, &["Pad -righty"], 0.7),
+            (Action::OrbitDown, &["Pad +righty"], 0.7),
+            (Action::RotateCamera, &["Mouse Middle"], 0.01),
+            (Action::RotateLight, &["Ctrl+Mouse Middle"], 0.01),
+            (Action::MoveLight, &["Mouse Right"], 0.5),
+            (Action::LightIntensity, &["Wheel"], 0.5),
+            (Action::LightBrighter, &["Pad +righttrigger"], 12.0),
+            (Action::LightDimmer, &["Pad +lefttrigger"], 12.0),
+            (Action::GizmoTranslate, &["T"], 1.0),
+            (Action::GizmoRotate, &["R"], 1.0),
+            (Action::GizmoScale, &["E"], 1.0),
+            (Action::AxisX, &["X"], 1.0),
+            (Action::AxisY, &["Y"], 1.0),
+            (Action::AxisZ, &["Z"], 1.0),
+            (Action::Snap, &["Left Shift"], 1.0),
+            (Action::SaveScene, &["F5"], 1.0),
+            (Action::RecordPath, &["F9"], 1.0),
+            (Action::PlayPath, &["F10"], 1.0),
+            (Action::Screenshot, &["F12"], 1.0),
+            (Action::SwitchBackend, &["F8"], 1.0),
+            (Action::ToggleOverlay, &["F1"], 1.0),
+            (Action::ToggleShadows, &["F2"], 1.0),
+            (Action::ToggleLighting, &["F3"], 1.0),
+            (Action::CycleDebugView, &["F4"], 1.0),
+            (Action::Bookmark(0), &["0"], 1.0),
+            (Action::Bookmark(1), &["1"], 1.0),
+            (Action::Bookmark(2), &["2"], 1.0),
+            (Action::Bookmark(3), &["3"], 1.0),
+            (Action::Bookmark(4), &["4"], 1.0),
+            (Action::Bookmark(5), &["5"], 1.0),
+            (Action::Bookmark(6), &["6"], 1.0),
+            (Action::Bookmark(7), &["7"], 1.0),
+            (Action::Bookmark(8), &["8"], 1.0),
+            (Action::Bookmark(9), &["9"], 1.0),
+            (Action::SaveBookmark(0), &["Ctrl+0"], 1.0),
+            (Action::SaveBookmark(1), &["Ctrl+1"], 1.0),
+            (Action::SaveBookmark(2), &["Ctrl+2"], 1.0),
+            (Action::SaveBookmark(3), &["Ctrl+3"], 1.0),
+            (Action::SaveBookmark(4), &["Ctrl+4"], 1.0),
+            (Action::SaveBookmark(5), &["Ctrl+5"], 1.0),
+            (Action::SaveBookmark(6), &["Ctrl+6"], 1.0),
+            (Action::SaveBookmark(7), &["Ctrl+7"], 1.0),
+            (Action::SaveBookmark(8), &["Ctrl+8"], 1.0),
+            (Action::SaveBookmark(9), &["Ctrl+9"], 1.0),
+            (Action::Quit, &["Escape", "Pad back"], 1.0),
+        ];
+        for (action, bindings, sensitivity) in defaults {
+            for binding in bindings {
+                input_map.bind(action, Binding::parse(binding).unwrap());
+            }
+            input_map.sensitivity.insert(action, sensitivity);
+        }
+        input_map
+    }
+
+    /// Applies the bindings, sensitivities and movement settings of a file on top of the current ones.
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        let mut rebound = Vec::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid binding line: {}", line));
+            let mut parts = line.splitn(3, ' ');
+            let command = parts.next().ok_or_else(invalid)?;
+            if let Some(setting) = match command {
+                "acceleration" => Some(&mut self.acceleration),
+                "damping" => Some(&mut self.damping),
+                "smoothing" => Some(&mut self.smoothing),
+                _ => None,
+            } {
+                *setting = parts.next().and_then(|value| value.parse().ok()).ok_or_else(invalid)?;
+                continue;
+            }
+            let action = parts.next().and_then(Action::from_name).ok_or_else(invalid)?;
+            let argument = parts.next().map(|argument| argument.trim());
+
+            match (command, argument) {
+                ("bind", Some(argument)) => {
+                    let binding = Binding::parse(argument).ok_or_else(invalid)?;
+                    if !rebound.contains(&action) {
+                        self.unbind(action);
+                        rebound.push(action);
+                    }
+                    self.bind(action, binding);
+                }
+                ("unbind", None) => self.unbind(action),
+                ("sensitivity", Some(argument)) => {
+                    let sensitivity = argument.parse().map_err(|_| invalid())?;
+                    self.sensitivity.insert(action, sensitivity);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        self.bindings.push((action, binding));
+    }
+
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.retain(|(bound_action, _)| *bound_action != action);
+    }
+
+    /// The action of an input under the held modifiers. When several match, the binding asking for
+    /// the most modifiers wins, so `Ctrl+Mouse Middle` takes over from `Mouse Middle` while Ctrl is held.
+    pub fn action(&self, input: Input, modifiers: Modifiers) -> Option<Action> {
+        self.bindings.iter()
+            .filter(|(_, binding)| binding.input == input && modifiers.contains(binding.modifiers))
+            .max_by_key(|(_, binding)| binding.modifiers.count())
+            .map(|(action, _)| *action)
+    }
+
+    pub fn sensitivity(&self, action: Action) -> f32 {
+        self.sensitivity.get(&action).copied().unwrap_or(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CTRL: Modifiers = Modifiers { ctrl: true, shift: false, alt: false };
+
+    #[test]
+    fn bindings_parse_modifiers_mouse_wheel_pad_and_keys() {
+        let parse = |text| Binding::parse(text).map(|binding| (binding.input, binding.modifiers));
+        assert_eq!(parse("W"), Some((Input::Key(Keycode::W), Modifiers::default())));
+        assert_eq!(parse(" Left Shift "), Some((Input::Key(Keycode::LShift), Modifiers::default())));
+        assert_eq!(parse("Ctrl+Mouse Middle"), Some((Input::Mouse(MouseButton::Middle), CTRL)));
+        assert_eq!(parse("Alt+Shift+Wheel"), Some((Input::Wheel, Modifiers { ctrl: false, shift: true, alt: true })));
+        assert_eq!(parse("Pad a"), Some((Input::PadButton(controller::Button::A), Modifiers::default())));
+        assert_eq!(parse("Pad -lefty"), Some((Input::PadAxis(controller::Axis::LeftY, false), Modifiers::default())));
+        assert_eq!(parse("Pad +righttrigger"), Some((Input::PadAxis(controller::Axis::TriggerRight, true), Modifiers::default())));
+
+        for invalid in ["", "Ctrl+", "Mouse Top", "Pad nothing", "Pad +nothing", "NoSuchKey"] {
+            assert_eq!(parse(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn loading_replaces_the_defaults_of_rebound_actions_only() {
+        let path = std::env::temp_dir().join("input_map_load.txt");
+        fs::write(&path, "\
+# comment
+bind camera_forward Up
+bind camera_forward Pad a
+unbind ref_forward
+bind light_dimmer Wheel
+sensitivity light_dimmer 2.5
+acceleration 100
+damping 4
+smoothing 0.25
+").unwrap();
+        let mut input_map = InputMap::new();
+        input_map.load(&path).unwrap();
+
+        let none = Modifiers::default();
+        assert_eq!(input_map.action(Input::Key(Keycode::Up), none), Some(Action::CameraForward));
+        assert_eq!(input_map.action(Input::PadButton(controller::Button::A), none), Some(Action::CameraForward));
+        assert_eq!(input_map.action(Input::Key(Keycode::W), none), None);
+        assert_eq!(input_map.action(Input::PadButton(controller::Button::DPadUp), none), None);
+        assert_eq!(input_map.action(Input::Key(Keycode::S), none), Some(Action::CameraBackward));
+        // Both light_intensity and light_dimmer are on the wheel now, the later binding wins
+        assert_eq!(input_map.action(Input::Wheel, none), Some(Action::LightDimmer));
+        assert_eq!(input_map.sensitivity(Action::LightDimmer), 2.5);
+        assert_eq!(input_map.sensitivity(Action::LightIntensity), 0.5);
+        assert_eq!((input_map.acceleration, input_map.damping, input_map.smoothing), (100.0, 4.0, 0.25));
+
+        for invalid in ["bind camera_forward", "bind fly_away W", "bind camera_forward NoSuchKey", "unbind camera_forward W", "sensitivity camera_forward fast", "damping"] {
+            fs::write(&path, invalid).unwrap();
+            let error = InputMap::new().load(&path).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", invalid);
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn the_binding_with_the_most_held_modifiers_wins() {
+        let middle = Input::Mouse(MouseButton::Middle);
+        let mut input_map = InputMap::new();
+        assert_eq!(input_map.action(middle, Modifiers::default()), Some(Action::RotateCamera));
+        assert_eq!(input_map.action(middle, CTRL), Some(Action::RotateLight));
+        // Extra held modifiers still match the bindings that do not ask for them
+        assert_eq!(input_map.action(middle, Modifiers { shift: true, ..CTRL }), Some(Action::RotateLight));
+        assert_eq!(input_map.action(middle, Modifiers { shift: true, ..Modifiers::default() }), Some(Action::RotateCamera));
+
+        input_map.bind(Action::MoveLight, Binding::parse("Ctrl+Shift+Mouse Middle").unwrap());
+        assert_eq!(input_map.action(middle, Modifiers { shift: true, ..CTRL }), Some(Action::MoveLight));
+        assert_eq!(input_map.action(middle, CTRL), Some(Action::RotateLight));
+    }
+}
@@ -1 +1,2 @@
 pub mod event_handler;
+pub mod input_map;
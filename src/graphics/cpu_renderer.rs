@@ -1,44 +1,163 @@
+use std::time::Instant;
 use sdl2::{pixels::PixelFormatEnum, render::TextureCreator, video::WindowContext};
-use crate::types::{light::Light, pixel::Pixel, renderer::Renderer, view_state::ViewState};
-use super::operations::Operations;
+use crate::types::{engine_error::EngineError, frame_stats::FrameStats, light::{Light, SHADOW_FACTOR}, overlay_image::OverlayImage, pick::Pick, pixel::{Pixel, OVERLAY_ID}};
+use crate::types::{render_settings::{DebugView, RenderSettings}, renderer::Renderer, view_state::ViewState};
+use super::{debug_views::{depth_gray, depth_range, heat, id_color, normal_color, LIT, SHADOWED}, operations::Operations, pixel_store::PixelStore};
+
+/// How far behind the nearest depth seen from the light a pixel still counts as lit, as in the lighting shader.
+const LIGHT_TOLERANCE: f32 = 4.0;
+
+/// Where a pixel lands on the light-space map.
+#[derive(Clone, Copy)]
+struct LightSplat {
+    projected: (i32, i32),
+    block_size: i32,
+    depth: f32,
+}
 
 pub struct CpuRenderer<'a> {
+    output: Option<WindowOutput<'a>>,
+    pixels: PixelStore,
+    canvas_width: u32,
+    canvas_height: u32,
+    pixel_map: Vec<i32>,
+    depth_buffer: Vec<f32>,
+    highlight_id: Option<u32>,
+    last_view_state: Option<ViewState>,
+    frame: Vec<u8>,
+    profiling: bool,
+    pass_timings: Vec<(&'static str, f32)>,
+    settings: RenderSettings,
+    overlay: Option<OverlayImage>,
+    frame_stats: FrameStats,
+}
+
+/// Where frames are shown, headless renderers only keep them for `screenshot`.
+struct WindowOutput<'a> {
     canvas: sdl2::render::Canvas<sdl2::video::Window>,
     texture_creator: &'a TextureCreator<WindowContext>,
     texture: sdl2::render::Texture<'a>,
-    pixels: Vec<Pixel>,
-    canvas_width: u32,
-    canvas_height: u32,
 }
 
 impl CpuRenderer<'_> {
     pub fn new<'a>(
         canvas: sdl2::render::Canvas<sdl2::video::Window>,
         texture_creator: &'a TextureCreator<WindowContext>,
-    ) -> CpuRenderer<'a> {
-        let (canvas_width, canvas_height) = canvas.output_size().unwrap();
+    ) -> Result<CpuRenderer<'a>, EngineError> {
+        let (canvas_width, canvas_height) = canvas.output_size().map_err(EngineError::Window)?;
         let texture = texture_creator
         .create_texture_streaming(PixelFormatEnum::RGBA8888, canvas_width, canvas_height)
-        .unwrap();
+        .map_err(|error| EngineError::Window(error.to_string()))?;
+        let mut renderer = CpuRenderer::headless(canvas_width, canvas_height);
+        renderer.output = Some(WindowOutput { canvas, texture_creator, texture });
+        Ok(renderer)
+    }
+
+    /// Draws one of the frame's buffers in place of the shaded colors, leaving overlays as they are.
+    fn debug_frame(&self, view_state: &ViewState, light: &Light, pixel_map: &[i32], depth_buffer: &[f32], overdraw: &[u32]) -> Vec<u8> {
+        let view = self.settings.debug_view;
+        let pixels = self.pixels.pixels();
+        let (light_depth, shadowed) = if view.uses_shadows() && self.settings.shadows {
+            self.light_pass(view_state, light)
+        } else {
+            (vec![-f32::INFINITY; pixel_map.len()], vec![false; pixels.len()])
+        };
+        let range = depth_range(depth_buffer).unwrap_or_default();
+        let light_range = depth_range(&light_depth).unwrap_or_default();
+        // Every pixel is projected with the scale at the reference point
+        let footprint = (view_state.camera_z - view_state.ref_z) / view_state.scale;
+        let (width, height) = (self.canvas_width as usize, self.canvas_height as usize);
+
+        pixel_map.iter().enumerate().flat_map(|(depth_index, &index)| {
+            let drawn = usize::try_from(index).ok().map(|index| (index, pixels[index]));
+            let color = match (view, drawn) {
+                (_, Some((_, pixel))) if pixel.id == OVERLAY_ID => (pixel.r, pixel.g, pixel.b),
+                (DebugView::Overdraw, _) => heat(overdraw[depth_index]),
+                (DebugView::ShadowMap, _) if light_depth[depth_index].is_finite() => depth_gray(light_depth[depth_index], light_range),
+                (DebugView::Depth, Some(_)) => depth_gray(depth_buffer[depth_index], range),
+                (DebugView::ObjectId, Some((_, pixel))) => id_color(pixel.id),
+                (DebugView::ShadowMask, Some((index, _))) => if shadowed[index] { SHADOWED } else { LIT },
+                (DebugView::Normals, Some(_)) => normal_color(depth_buffer, width, height, depth_index, footprint),
+                _ => (0.0, 0.0, 0.0),
+            };
+            [255, (color.2 * 255.0) as u8, (color.1 * 255.0) as u8, (color.0 * 255.0) as u8]
+        }).collect()
+    }
+
+    /// What the gpu renderer's raytracing and lighting passes find: the nearest depth seen from the light on a
+    /// light-space map the size of the frame, and whether each pixel is hidden from the light by another.
+    fn light_pass(&self, view_state: &ViewState, light: &Light) -> (Vec<f32>, Vec<bool>) {
+        let (width, height) = (self.canvas_width as i32, self.canvas_height as i32);
+        let scale_factor = view_state.scale / light.z;
+        let splats: Vec<Option<LightSplat>> = self.pixels.pixels().iter().map(|pixel| {
+            if pixel.id == OVERLAY_ID {
+                return None;
+            }
+            let (x, y, z) = Operations::rotate((pixel.x, pixel.y, pixel.z), (view_state.angle_x, view_state.angle_y, view_state.angle_z));
+            let (x, y, z) = Operations::rotate(
+                (x + view_state.ref_x, y + view_state.ref_y, z + view_state.ref_z - light.z),
+                (-view_state.l_angle_x, -view_state.l_angle_y, -view_state.l_angle_z)
+            );
+            let positioned = (x - light.x, y - light.y, z + light.z);
+            if light.z - positioned.2 < view_state.z_offset {
+                return None;
+            }
+            let projected = Operations::project(positioned, scale_factor, width as f32, height as f32);
+            Some(LightSplat { projected, block_size: (scale_factor * pixel.size_factor).ceil() as i32, depth: positioned.2 })
+        }).collect();
+
+        let positions = |splat: LightSplat| {
+            let ((x, y), block_size) = (splat.projected, splat.block_size);
+            (0..block_size)
+                .flat_map(move |dx| (0..block_size).map(move |dy| (x + dx, y + dy)))
+                .filter(move |&(x, y)| x >= 0 && y >= 0 && x < width && y < height)
+                .map(move |(x, y)| (y * width + x) as usize)
+        };
+        let mut light_depth = vec![-f32::INFINITY; (width * height) as usize];
+        for &splat in splats.iter().flatten() {
+            for i in positions(splat) {
+                light_depth[i] = light_depth[i].max(splat.depth);
+            }
+        }
+        let shadowed = splats
+            .iter()
+            .map(|splat| splat.is_some_and(|splat| !positions(splat).any(|i| (light_depth[i] - splat.depth).abs() < LIGHT_TOLERANCE)))
+            .collect();
+        (light_depth, shadowed)
+    }
+
+    /// Renders without a window, frames are read back with `screenshot`.
+    pub fn headless(width: u32, height: u32) -> CpuRenderer<'static> {
         CpuRenderer {
-            canvas,
-            texture_creator,
-            texture,
-            pixels: Vec::new(),
-            canvas_width,
-            canvas_height,
+            output: None,
+            pixels: PixelStore::new(),
+            canvas_width: width,
+            canvas_height: height,
+            pixel_map: Vec::new(),
+            depth_buffer: Vec::new(),
+            highlight_id: None,
+            last_view_state: None,
+            frame: vec![0; (width * height * 4) as usize],
+            profiling: false,
+            pass_timings: Vec::new(),
+            settings: RenderSettings::default(),
+            overlay: None,
+            frame_stats: FrameStats::default(),
         }
     }
 }
 
 impl Renderer<'_> for CpuRenderer<'_> {
-    fn render(&mut self, view_state: &ViewState, light: &Light) {
+    fn render(&mut self, view_state: &ViewState, light: &Light) -> Result<(), EngineError> {
+        let projection_start = Instant::now();
         let size = (self.canvas_width * self.canvas_height) as usize;
         let mut pixel_map: Vec<i32> = vec![-1; size];
-        let mut pixel_transformations: Vec<(f32, f32, f32)> = vec![(0.0, 0.0, 0.0); size];
+        let mut pixel_transformations: Vec<(f32, f32, f32)> = Vec::with_capacity(self.pixels.pixels().len());
         let mut depth_buffer = vec![-f32::INFINITY; size];
+        let mut culled = 0;
+        let mut overdraw = (self.settings.debug_view == DebugView::Overdraw).then(|| vec![0u32; size]);
 
-        for (i, pixel) in self.pixels.iter().enumerate() {
+        for (i, pixel) in self.pixels.pixels().iter().enumerate() {
             let mut trasnformed_pixel = Operations::rotate(
                 (pixel.x, pixel.y, pixel.z),
                 (view_state.angle_x, view_state.angle_y, view_state.angle_z)
@@ -62,7 +181,10 @@ impl Renderer<'_> for CpuRenderer<'_> {
             positioned_pixel.1 += view_state.camera_y;
             positioned_pixel.2 += view_state.camera_z;
 
-            if view_state.camera_z - positioned_pixel.2 < view_state.z_offset { continue; }
+            if view_state.camera_z - positioned_pixel.2 < view_state.z_offset {
+                culled += 1;
+                continue;
+            }
 
             let scale_factor = view_state.scale / (view_state.camera_z - view_state.ref_z);
 
@@ -74,45 +196,187 @@ impl Renderer<'_> for CpuRenderer<'_> {
             );
 
             let block_size = (scale_factor * pixel.size_factor).ceil() as i32;
+            if projected.0 + block_size <= 0 || projected.1 + block_size <= 0 || projected.0 >= self.canvas_width as i32 || projected.1 >= self.canvas_height as i32 {
+                culled += 1;
+                continue;
+            }
+            if let Some(counts) = overdraw.as_mut() {
+                for (x, y) in (0..block_size).flat_map(|dx| (0..block_size).map(move |dy| (projected.0 + dx, projected.1 + dy))) {
+                    if x >= 0 && y >= 0 && x < self.canvas_width as i32 && y < self.canvas_height as i32 {
+                        counts[(y * self.canvas_width as i32 + x) as usize] += 1;
+                    }
+                }
+            }
+
+            // Overlays are drawn in front of everything else
+            let depth = if pixel.id == OVERLAY_ID { f32::INFINITY } else { positioned_pixel.2 };
 
-            Operations::draw_pixel(&mut pixel_map, &mut depth_buffer, self.canvas_width as i32, self.canvas_height as i32, projected.0, projected.1, block_size, positioned_pixel.2, i as u32);
+            Operations::draw_pixel(&mut pixel_map, &mut depth_buffer, self.canvas_width as i32, self.canvas_height as i32, projected.0, projected.1, block_size, depth, i as u32);
         }
 
-        let pixel_data: Vec<u8> = pixel_map.iter().flat_map(|&index| {
-            if index == -1 { return vec![0, 0, 0, 0]}
-            let index = index as usize;
-            let pixel = self.pixels[index];
+        let shading_start = Instant::now();
+        let mut pixel_data: Vec<u8> = if self.settings.debug_view != DebugView::Shaded {
+            self.debug_frame(view_state, light, &pixel_map, &depth_buffer, overdraw.as_deref().unwrap_or_default())
+        } else {
+            // Pixels hidden from the light keep a share of their unlit color, as in the projection shader
+            let shadowed = self.settings.shadows.then(|| self.light_pass(view_state, light).1);
+            pixel_map.iter().flat_map(|&index| {
+                if index == -1 { return vec![0, 0, 0, 0]}
+                let index = index as usize;
+                let pixel = self.pixels.pixels()[index];
 
-            let lit_color = Operations::apply_lighting(
-                pixel_transformations[index],
-                (pixel.r, pixel.g, pixel.b), 
-                (light.x, light.y, light.z), 
-                light.intensity
-            );
+                let lit_color = if shadowed.as_ref().is_some_and(|shadowed| shadowed[index]) {
+                    (pixel.b * SHADOW_FACTOR, pixel.g * SHADOW_FACTOR, pixel.r * SHADOW_FACTOR)
+                } else if self.settings.lighting {
+                    Operations::apply_lighting(
+                        pixel_transformations[index],
+                        (pixel.r, pixel.g, pixel.b), 
+                        (light.x, light.y, light.z), 
+                        light.intensity
+                    )
+                } else {
+                    (pixel.b, pixel.g, pixel.r)
+                };
+                let lit_color = if pixel.id == OVERLAY_ID {
+                    (pixel.b, pixel.g, pixel.r)
+                } else if self.highlight_id == Some(pixel.id) {
+                    ((lit_color.0 + 0.2) / 2.0, (lit_color.1 + 0.8) / 2.0, (lit_color.2 + 1.0) / 2.0)
+                } else {
+                    lit_color
+                };
 
-            vec![
-                (pixel.a * 255.0) as u8,
-                (lit_color.0 * 255.0) as u8,
-                (lit_color.1 * 255.0) as u8,
-                (lit_color.2 * 255.0) as u8,
-            ]
-        }).collect();        
+                vec![
+                    (pixel.a * 255.0) as u8,
+                    (lit_color.0 * 255.0) as u8,
+                    (lit_color.1 * 255.0) as u8,
+                    (lit_color.2 * 255.0) as u8,
+                ]
+            }).collect()
+        };
+        if self.settings.stats {
+            let mut drawn = vec![false; self.pixels.pixels().len()];
+            for &index in pixel_map.iter().filter(|&&index| index >= 0) {
+                drawn[index as usize] = true;
+            }
+            self.frame_stats = FrameStats { visible: drawn.iter().filter(|&&drawn| drawn).count(), culled };
+        }
+        if let Some(overlay) = &self.overlay {
+            let (columns, rows) = overlay.visible_size(self.canvas_width, self.canvas_height);
+            for row in 0..rows as usize {
+                let texels = &overlay.texels[row * overlay.width as usize..][..columns as usize];
+                let start = ((overlay.y as usize + row) * self.canvas_width as usize + overlay.x as usize) * 4;
+                for (target, texel) in pixel_data[start..start + columns as usize * 4].chunks_exact_mut(4).zip(texels) {
+                    target.copy_from_slice(&texel.to_le_bytes());
+                }
+            }
+        }
+        self.pixel_map = pixel_map;
+        self.depth_buffer = depth_buffer;
+        self.last_view_state = Some(*view_state);
         
-        self.texture.update(None, &pixel_data, self.canvas_width as usize * 4).unwrap();
-        self.canvas.clear();
-        self.canvas.copy(&self.texture, None, None).unwrap();
-        self.canvas.present();
+        if let Some(output) = self.output.as_mut() {
+            output.texture.update(None, &pixel_data, self.canvas_width as usize * 4).map_err(|error| EngineError::Window(error.to_string()))?;
+            output.canvas.clear();
+            output.canvas.copy(&output.texture, None, None).map_err(EngineError::Window)?;
+            output.canvas.present();
+        }
+        self.frame = pixel_data;
+        if self.profiling {
+            let milliseconds = |duration: std::time::Duration| duration.as_secs_f32() * 1000.0;
+            self.pass_timings = vec![
+                ("projection", milliseconds(shading_start - projection_start)),
+                ("shading", milliseconds(shading_start.elapsed())),
+            ];
+        }
+        Ok(())
     }
 
     fn load_pixels(&mut self, new_pixels: Vec<Pixel>) {
-        self.pixels.extend(new_pixels);
+        self.pixels.load(new_pixels);
+    }
+
+    fn add_object(&mut self, id: u32, pixels: Vec<Pixel>) {
+        self.pixels.add(id, pixels);
+    }
+
+    fn update_object(&mut self, id: u32, pixels: Vec<Pixel>) {
+        self.pixels.update(id, pixels);
+    }
+
+    fn remove_object(&mut self, id: u32) {
+        self.pixels.remove(id);
+    }
+
+    fn pick(&mut self, x: i32, y: i32) -> Option<Pick> {
+        let view_state = self.last_view_state?;
+        if x < 0 || y < 0 || x >= self.canvas_width as i32 || y >= self.canvas_height as i32 {
+            return None;
+        }
+
+        let depth_index = (y * self.canvas_width as i32 + x) as usize;
+        let pixel_index = usize::try_from(*self.pixel_map.get(depth_index)?).ok()?;
+        let pixel = self.pixels.pixels().get(pixel_index)?;
+
+        let (x, y, z) = Operations::rotate(
+            (pixel.x, pixel.y, pixel.z),
+            (view_state.angle_x, view_state.angle_y, view_state.angle_z)
+        );
+        Some(Pick {
+            object_id: pixel.id,
+            pixel_index,
+            x: x + view_state.ref_x,
+            y: y + view_state.ref_y,
+            z: z + view_state.ref_z,
+            depth: self.depth_buffer[depth_index],
+        })
+    }
+
+    fn set_highlight(&mut self, id: Option<u32>) {
+        self.highlight_id = id;
     }
-    
-    fn resize(&mut self, width: u32, height: u32) {
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), EngineError> {
+        if let Some(output) = self.output.as_mut() {
+            output.texture = output.texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGBA8888, width, height)
+            .map_err(|error| EngineError::Window(error.to_string()))?;
+        }
         self.canvas_width = width;
         self.canvas_height = height;
-        self.texture = self.texture_creator
-        .create_texture_streaming(PixelFormatEnum::RGBA8888, width, height)
-        .unwrap();
+        self.frame = vec![0; (width * height * 4) as usize];
+        Ok(())
+    }
+
+    fn screenshot(&mut self) -> Result<(Vec<u8>, u32, u32), EngineError> {
+        Ok((self.frame.clone(), self.canvas_width, self.canvas_height))
+    }
+
+    fn backend(&self) -> String {
+        String::from("cpu")
+    }
+
+    fn pixel_count(&self) -> usize {
+        self.pixels.pixels().len()
+    }
+
+    fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+        self.pass_timings.clear();
+    }
+
+    fn pass_timings(&self) -> Vec<(&'static str, f32)> {
+        self.pass_timings.clone()
+    }
+
+    fn set_render_settings(&mut self, settings: RenderSettings) {
+        self.settings = settings;
+    }
+
+    fn set_overlay(&mut self, overlay: Option<OverlayImage>) {
+        self.overlay = overlay;
+    }
+
+    fn frame_stats(&self) -> Option<FrameStats> {
+        self.settings.stats.then_some(self.frame_stats)
     }
 }
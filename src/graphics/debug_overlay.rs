@@ -0,0 +1,227 @@
+#![allow(dead_code)]
+use std::collections::VecDeque;
+use crate::types::{frame_stats::FrameStats, light::Light, overlay_image::OverlayImage, render_settings::RenderSettings, view_state::ViewState};
+
+/// Frame times kept for the graph, one bar each.
+const HISTORY: usize = 120;
+const MARGIN: u32 = 8;
+const PADDING: u32 = 6;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 3;
+/// Characters per line, longer lines are cut off.
+const COLUMNS: usize = 64;
+const BAR_WIDTH: u32 = 3;
+const GRAPH_HEIGHT: u32 = 40;
+/// The frame time in milliseconds at the top of the graph, unless a slower frame widens it.
+const GRAPH_SCALE: f32 = 1000.0 / 30.0;
+const TARGET_FRAME_TIME: f32 = 1000.0 / 60.0;
+
+const BACKGROUND: u32 = rgba(16, 16, 20);
+const TEXT: u32 = rgba(230, 230, 230);
+const GUIDE: u32 = rgba(90, 90, 90);
+const FAST: u32 = rgba(80, 200, 120);
+const SLOW: u32 = rgba(230, 190, 60);
+const STALLED: u32 = rgba(230, 70, 60);
+
+/// Packs a color like the frame's texels, red in the highest byte and alpha in the lowest.
+const fn rgba(r: u8, g: u8, b: u8) -> u32 {
+    (r as u32) << 24 | (g as u32) << 16 | (b as u32) << 8 | 0xff
+}
+
+/// What the overlay reports on, gathered by the loop each frame.
+pub struct OverlayInfo<'a> {
+    pub backend: &'a str,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_count: usize,
+    pub stats: Option<FrameStats>,
+    pub view_state: &'a ViewState,
+    pub light: &'a Light,
+    pub settings: RenderSettings,
+}
+
+/// A panel in the corner of the window with frame statistics, the view and the render settings.
+pub struct DebugOverlay {
+    pub visible: bool,
+    frame_times: VecDeque<f32>,
+}
+
+impl DebugOverlay {
+    pub fn new() -> DebugOverlay {
+        DebugOverlay { visible: false, frame_times: VecDeque::with_capacity(HISTORY) }
+    }
+
+    pub fn record(&mut self, milliseconds: f32) {
+        if self.frame_times.len() == HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(milliseconds);
+    }
+
+    pub fn lines(&self, info: &OverlayInfo) -> Vec<String> {
+        let last = self.frame_times.back().copied().unwrap_or(0.0);
+        let mean = self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32;
+        let max = self.frame_times.iter().copied().fold(0.0, f32::max);
+        let (v, l) = (info.view_state, info.light);
+        let (visible, culled) = match info.stats {
+            Some(stats) => (stats.visible.to_string(), stats.culled.to_string()),
+            None => (String::from("-"), String::from("-")),
+        };
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        vec![
+            info.backend.to_string(),
+            format!("{}x{}  frame {:.2} ms  {:.0} fps  max {:.2} ms", info.width, info.height, last, 1000.0 / mean.max(f32::EPSILON), max),
+            format!("pixels {}  visible {}  culled {}", info.pixel_count, visible, culled),
+            format!("camera {:.1} {:.1} {:.1}  turn {:.2} {:.2} {:.2}", v.camera_x, v.camera_y, v.camera_z, v.c_angle_x, v.c_angle_y, v.c_angle_z),
+            format!("scene {:.2} {:.2} {:.2}  ref {:.1} {:.1} {:.1}", v.angle_x, v.angle_y, v.angle_z, v.ref_x, v.ref_y, v.ref_z),
+            format!("light {:.1} {:.1} {:.1}  intensity {:.1}  turn {:.2} {:.2} {:.2}", l.x, l.y, l.z, l.intensity, v.l_angle_x, v.l_angle_y, v.l_angle_z),
+            format!("shadows {}  lighting {}  view {}", on_off(info.settings.shadows), on_off(info.settings.lighting), info.settings.debug_view.name()),
+        ]
+    }
+
+    /// Draws the panel, placed in the top left corner of the window.
+    pub fn draw(&self, info: &OverlayInfo) -> OverlayImage {
+        let lines = self.lines(info);
+        let width = PADDING * 2 + COLUMNS as u32 * ADVANCE;
+        let graph_top = PADDING + lines.len() as u32 * LINE_HEIGHT;
+        let height = graph_top + GRAPH_HEIGHT + PADDING;
+        let mut image = OverlayImage { x: MARGIN, y: MARGIN, width, height, texels: vec![BACKGROUND; (width * height) as usize] };
+
+        for (row, line) in lines.iter().enumerate() {
+            for (column, character) in line.chars().take(COLUMNS).enumerate() {
+                draw_glyph(&mut image, PADDING + column as u32 * ADVANCE, PADDING + row as u32 * LINE_HEIGHT, character);
+            }
+        }
+
+        // Bars grow upwards from the bottom of the graph, with a line at the frame time of 60 fps
+        let scale = self.frame_times.iter().copied().fold(GRAPH_SCALE, f32::max);
+        let graph_bottom = graph_top + GRAPH_HEIGHT;
+        let bar_height = |milliseconds: f32| ((milliseconds / scale * GRAPH_HEIGHT as f32).round() as u32).min(GRAPH_HEIGHT);
+        let guide = graph_bottom - bar_height(TARGET_FRAME_TIME);
+        for x in PADDING..PADDING + HISTORY as u32 * BAR_WIDTH {
+            set(&mut image, x, guide, GUIDE);
+        }
+        for (bar, &milliseconds) in self.frame_times.iter().enumerate() {
+            let color = if milliseconds <= TARGET_FRAME_TIME { FAST } else if milliseconds <= GRAPH_SCALE { SLOW } else { STALLED };
+            let left = PADDING + bar as u32 * BAR_WIDTH;
+            for y in graph_bottom - bar_height(milliseconds)..graph_bottom {
+                for x in left..left + BAR_WIDTH - 1 {
+                    set(&mut image, x, y, color);
+                }
+            }
+        }
+        image
+    }
+}
+
+fn set(image: &mut OverlayImage, x: u32, y: u32, color: u32) {
+    if x < image.width && y < image.height {
+        image.texels[(y * image.width + x) as usize] = color;
+    }
+}
+
+fn draw_glyph(image: &mut OverlayImage, left: u32, top: u32, character: char) {
+    for (y, row) in glyph(character).iter().enumerate() {
+        for x in 0..GLYPH_WIDTH {
+            if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                set(image, left + x, top + y as u32, TEXT);
+            }
+        }
+    }
+}
+
+/// Rows of a 5x7 bitmap font, the highest of the five bits on the left. Letters are drawn upper case
+/// and characters without a glyph as a question mark.
+fn glyph(character: char) -> [u8; 7] {
+    match character.to_ascii_uppercase() {
+        ' ' => [0; 7],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        '=' => [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000],
+        '_' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111],
+        '/' => [0b00001, 0b00010, 0b00010, 0b00100, 0b01000, 0b01000, 0b10000],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '[' => [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110],
+        ']' => [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110],
+        '%' => [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::test_helper::{test_light, test_view_state};
+
+    #[test]
+    fn draws_text_and_frame_times() {
+        let mut overlay = DebugOverlay::new();
+        for frame in 0..HISTORY + 10 {
+            overlay.record(frame as f32);
+        }
+        let (view_state, light) = (test_view_state(), test_light());
+        let info = OverlayInfo {
+            backend: "gpu (Vulkan, Test Adapter)",
+            width: 800,
+            height: 600,
+            pixel_count: 1000,
+            stats: Some(FrameStats { visible: 600, culled: 50 }),
+            view_state: &view_state,
+            light: &light,
+            settings: RenderSettings::default(),
+        };
+
+        let lines = overlay.lines(&info);
+        assert_eq!(lines[2], "pixels 1000  visible 600  culled 50");
+        // Every character shown has a glyph of its own rather than the question mark
+        let unknown = glyph('?');
+        assert!(lines.concat().chars().filter(|&character| character != '?').all(|character| glyph(character) != unknown));
+
+        let image = overlay.draw(&info);
+        assert_eq!(image.texels.len(), (image.width * image.height) as usize);
+        assert!(image.texels.contains(&TEXT) && image.texels.contains(&STALLED));
+        assert_eq!(overlay.frame_times.len(), HISTORY);
+    }
+}
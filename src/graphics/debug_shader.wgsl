@@ -0,0 +1,180 @@
+struct Uniforms {
+    angle_x: f32,
+    angle_y: f32,
+    angle_z: f32,
+    c_angle_x: f32,
+    c_angle_y: f32,
+    c_angle_z: f32,
+    l_angle_x: f32,
+    l_angle_y: f32,
+    l_angle_z: f32,
+    scale: f32,
+    canvas_width: f32,
+    canvas_height: f32,
+    light_x: f32,
+    light_y: f32,
+    light_z: f32,
+    intensity: f32,
+    camera_x: f32,
+    camera_y: f32,
+    camera_z: f32,
+    ref_x: f32,
+    ref_y: f32,
+    ref_z: f32,
+    z_offset: f32,
+    highlight_id: u32,
+    flags: u32,
+    debug_view: u32,
+};
+
+const DEBUG_DEPTH: u32 = 1u;
+const DEBUG_OBJECT_ID: u32 = 2u;
+const DEBUG_SHADOW_MAP: u32 = 3u;
+const DEBUG_SHADOW_MASK: u32 = 4u;
+const DEBUG_NORMALS: u32 = 5u;
+const DEBUG_OVERDRAW: u32 = 6u;
+
+// Depth keys where nothing was drawn and where an overlay was, both left out of the depth range
+const EMPTY_KEY: u32 = 0u;
+const OVERLAY_KEY: u32 = 0xffffffffu;
+// Splats per screen position shown in red, fewer run through yellow and green down to blue
+const OVERDRAW_SCALE: f32 = 16.0;
+// Screen pixels to each side the depth slope of the normals view is measured over
+const NORMAL_RADIUS: i32 = 2;
+const LIT: vec3<f32> = vec3<f32>(1.0, 1.0, 1.0);
+const SHADOWED: vec3<f32> = vec3<f32>(0.15, 0.15, 0.5);
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var<storage, read_write> img: array<u32>;
+@group(0) @binding(2) var<storage, read> depth_buffer: array<u32>;
+@group(0) @binding(3) var<storage, read> depth_map_buffer: array<u32>;
+@group(0) @binding(4) var<storage, read> winner_buffer: array<u32>;
+@group(0) @binding(5) var<storage, read> shadow: array<u32>;
+@group(0) @binding(6) var<storage, read> light_depth_buffer: array<u32>;
+@group(0) @binding(7) var<storage, read> overdraw: array<u32>;
+// For the camera and then the light depth buffer, the largest key and the largest inverted key, which is the smallest
+@group(0) @binding(8) var<storage, read_write> depth_range: array<atomic<u32>, 4>;
+
+var<workgroup> workgroup_range: array<atomic<u32>, 4>;
+
+fn depth_from_key(key: u32) -> f32 {
+    if ((key & 0x80000000u) != 0u) {
+        return bitcast<f32>(key & 0x7fffffffu);
+    }
+    return bitcast<f32>(~key);
+}
+
+fn widen(slot: u32, key: u32) {
+    if (key != EMPTY_KEY && key != OVERLAY_KEY) {
+        atomicMax(&workgroup_range[slot], key);
+        atomicMax(&workgroup_range[slot + 1u], ~key);
+    }
+}
+
+// Range pass: the nearest and farthest depth seen from the camera and from the light
+@compute @workgroup_size(64)
+fn range(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32
+) {
+    let index = id.x + id.y * groups.x * 64u;
+    if (index < arrayLength(&img)) {
+        widen(0u, depth_buffer[index]);
+        widen(2u, light_depth_buffer[index]);
+    }
+    workgroupBarrier();
+    if (local_index < 4u) {
+        atomicMax(&depth_range[local_index], atomicLoad(&workgroup_range[local_index]));
+    }
+}
+
+// Nearest is white, farthest a dark gray so it stays apart from the empty background
+fn depth_gray(slot: u32, key: u32) -> vec3<f32> {
+    let near = depth_from_key(atomicLoad(&depth_range[slot]));
+    let far = depth_from_key(~atomicLoad(&depth_range[slot + 1u]));
+    var t = 1.0;
+    if (near > far) {
+        t = (depth_from_key(key) - far) / (near - far);
+    }
+    return vec3<f32>(0.15 + 0.85 * clamp(t, 0.0, 1.0));
+}
+
+// Spreads neighbouring ids over unrelated hues, never darker than a quarter
+fn id_color(id: u32) -> vec3<f32> {
+    let hash = id * 2654435761u;
+    let channels = vec3<f32>(f32((hash >> 24u) & 255u), f32((hash >> 16u) & 255u), f32((hash >> 8u) & 255u));
+    return 0.25 + 0.75 * channels / 255.0;
+}
+
+fn heat(count: u32) -> vec3<f32> {
+    if (count == 0u) {
+        return vec3<f32>(0.0);
+    }
+    let t = clamp((f32(count) - 1.0) / (OVERDRAW_SCALE - 1.0), 0.0, 1.0);
+    let ramp = 1.5 - abs(4.0 * t - vec3<f32>(3.0, 2.0, 1.0));
+    return clamp(ramp, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn neighbour_depth(x: i32, y: i32, fallback: f32) -> f32 {
+    let width = i32(uniforms.canvas_width);
+    if (x < 0 || y < 0 || x >= width || y >= i32(uniforms.canvas_height)) {
+        return fallback;
+    }
+    let key = depth_buffer[y * width + x];
+    if (key == EMPTY_KEY || key == OVERLAY_KEY) {
+        return fallback;
+    }
+    return depth_from_key(key);
+}
+
+// The depth slope between the neighbours on either side as a normal facing the camera, mapped to colors
+fn normal_color(index: u32) -> vec3<f32> {
+    let width = u32(uniforms.canvas_width);
+    let x = i32(index % width);
+    let y = i32(index / width);
+    let z = depth_from_key(depth_buffer[index]);
+    // World units one screen pixel spans at this depth
+    let footprint = max(uniforms.camera_z - z, uniforms.z_offset) / uniforms.scale;
+    let span = 2.0 * f32(NORMAL_RADIUS) * footprint;
+    let slope_x = (neighbour_depth(x + NORMAL_RADIUS, y, z) - neighbour_depth(x - NORMAL_RADIUS, y, z)) / span;
+    // Screen rows run downwards, world y upwards
+    let slope_y = (neighbour_depth(x, y - NORMAL_RADIUS, z) - neighbour_depth(x, y + NORMAL_RADIUS, z)) / span;
+    return normalize(vec3<f32>(-slope_x, -slope_y, 1.0)) * 0.5 + 0.5;
+}
+
+// Debug pass: replaces the shaded colors with one of the frame's buffers, leaving overlays as they are
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = id.x + id.y * groups.x * 64u;
+    if (index >= arrayLength(&img) || depth_buffer[index] == OVERLAY_KEY) {
+        return;
+    }
+
+    let winner = winner_buffer[index];
+    let view = uniforms.debug_view;
+    var color = vec3<f32>(0.0);
+    if (view == DEBUG_OVERDRAW) {
+        color = heat(overdraw[index]);
+    } else if (view == DEBUG_SHADOW_MAP) {
+        let key = light_depth_buffer[index];
+        if (key != EMPTY_KEY) {
+            color = depth_gray(2u, key);
+        }
+    } else if (winner != 0u) {
+        if (view == DEBUG_DEPTH) {
+            color = depth_gray(0u, depth_buffer[index]);
+        } else if (view == DEBUG_OBJECT_ID) {
+            color = id_color(depth_map_buffer[index]);
+        } else if (view == DEBUG_SHADOW_MASK) {
+            color = select(LIT, SHADOWED, shadow[winner - 1u] != 0u);
+        } else if (view == DEBUG_NORMALS) {
+            color = normal_color(index);
+        }
+    }
+
+    img[index] = u32(color.r * 255.0) << 24 |
+                 u32(color.g * 255.0) << 16 |
+                 u32(color.b * 255.0) << 8  |
+                 255u;
+}
@@ -0,0 +1,95 @@
+// Colors of the debug views for the cpu renderer, matching the functions of the same names in debug_shader.wgsl
+
+/// Splats per screen position shown in red, fewer run through yellow and green down to blue.
+pub const OVERDRAW_SCALE: f32 = 16.0;
+/// Screen pixels to each side the depth slope of the normals view is measured over.
+pub const NORMAL_RADIUS: usize = 2;
+pub const LIT: (f32, f32, f32) = (1.0, 1.0, 1.0);
+pub const SHADOWED: (f32, f32, f32) = (0.15, 0.15, 0.5);
+
+/// The farthest and nearest of the finite depths, None when there are none.
+pub fn depth_range(depths: &[f32]) -> Option<(f32, f32)> {
+    depths.iter().filter(|depth| depth.is_finite()).fold(None, |range, &depth| match range {
+        Some((far, near)) => Some((depth.min(far), depth.max(near))),
+        None => Some((depth, depth)),
+    })
+}
+
+/// Nearest is white, farthest a dark gray so it stays apart from the empty background.
+pub fn depth_gray(depth: f32, (far, near): (f32, f32)) -> (f32, f32, f32) {
+    let t = if near > far { (depth - far) / (near - far) } else { 1.0 };
+    let gray = 0.15 + 0.85 * t.clamp(0.0, 1.0);
+    (gray, gray, gray)
+}
+
+/// Spreads neighbouring ids over unrelated hues, never darker than a quarter.
+pub fn id_color(id: u32) -> (f32, f32, f32) {
+    let hash = id.wrapping_mul(2654435761);
+    let channel = |shift: u32| 0.25 + 0.75 * ((hash >> shift) & 255) as f32 / 255.0;
+    (channel(24), channel(16), channel(8))
+}
+
+pub fn heat(count: u32) -> (f32, f32, f32) {
+    if count == 0 {
+        return (0.0, 0.0, 0.0);
+    }
+    let t = ((count as f32 - 1.0) / (OVERDRAW_SCALE - 1.0)).clamp(0.0, 1.0);
+    let ramp = |center: f32| (1.5 - (4.0 * t - center).abs()).clamp(0.0, 1.0);
+    (ramp(3.0), ramp(2.0), ramp(1.0))
+}
+
+/// The depth slope between the neighbours on either side as a normal facing the camera, mapped to colors.
+/// `footprint` is how many world units one screen pixel spans.
+pub fn normal_color(depths: &[f32], width: usize, height: usize, index: usize, footprint: f32) -> (f32, f32, f32) {
+    let (x, y) = ((index % width) as isize, (index / width) as isize);
+    let z = depths[index];
+    let neighbour = |x: isize, y: isize| {
+        if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+            return z;
+        }
+        let depth = depths[y as usize * width + x as usize];
+        if depth.is_finite() { depth } else { z }
+    };
+    let radius = NORMAL_RADIUS as isize;
+    let span = 2.0 * NORMAL_RADIUS as f32 * footprint;
+    let slope_x = (neighbour(x + radius, y) - neighbour(x - radius, y)) / span;
+    // Screen rows run downwards, world y upwards
+    let slope_y = (neighbour(x, y - radius) - neighbour(x, y + radius)) / span;
+    let length = (slope_x * slope_x + slope_y * slope_y + 1.0).sqrt();
+    (0.5 - 0.5 * slope_x / length, 0.5 - 0.5 * slope_y / length, 0.5 + 0.5 / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use crate::graphics::{cpu_renderer::CpuRenderer, gpu_renderer::{AdapterOptions, GpuRenderer}};
+    use crate::helpers::golden_helper::{reference_scenes, render_scene, REFERENCE_SIZE};
+    use crate::types::{render_settings::{DebugView, RenderSettings}, renderer::Renderer};
+
+    #[test]
+    fn every_view_shows_something_of_its_own() {
+        let options = AdapterOptions { backends: wgpu::Backends::all(), allow_software: true };
+        let (width, height) = REFERENCE_SIZE;
+        let mut backends: Vec<Box<dyn Fn() -> Box<dyn Renderer<'static>>>> = vec![Box::new(move || Box::new(CpuRenderer::headless(width, height)))];
+        if task::block_on(GpuRenderer::headless(1, 1, &options)).is_ok() {
+            backends.push(Box::new(move || Box::new(task::block_on(GpuRenderer::headless(width, height, &options)).unwrap())));
+        }
+
+        // The shadow scene has two objects, one shadowing the other
+        let scene = reference_scenes().into_iter().find(|scene| scene.name == "shadow").unwrap();
+        for create in backends {
+            let mut frames: Vec<Vec<u8>> = Vec::new();
+            for view in DebugView::ALL {
+                let mut renderer = create();
+                renderer.set_render_settings(RenderSettings { debug_view: view, ..RenderSettings::default() });
+                let (frame, _, _) = render_scene(renderer.as_mut(), &scene).unwrap();
+                assert!(frame.iter().any(|&value| value > 0), "{} shows nothing on {}", view.name(), renderer.backend());
+                assert!(!frames.contains(&frame), "{} looks like another view on {}", view.name(), renderer.backend());
+                frames.push(frame);
+            }
+        }
+        assert_eq!(heat(1), (0.0, 0.0, 0.5));
+        assert_ne!(id_color(1), id_color(2));
+    }
+}
@@ -0,0 +1,44 @@
+use sdl2::{pixels::PixelFormatEnum, render::TextureCreator, video::WindowContext};
+use crate::types::engine_error::EngineError;
+
+pub struct Display<'a> {
+    canvas: sdl2::render::Canvas<sdl2::video::Window>,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: sdl2::render::Texture<'a>,
+}
+
+impl Display<'_> {
+    pub fn new<'a>(
+        canvas: sdl2::render::Canvas<sdl2::video::Window>,
+        texture_creator: &'a TextureCreator<WindowContext>,
+    ) -> Result<Display<'a>, EngineError> {
+        let (canvas_width, canvas_height) = canvas.output_size().map_err(EngineError::Window)?;
+        let texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGBA8888, canvas_width, canvas_height)
+            .map_err(|error| EngineError::Window(error.to_string()))?;
+        Ok(Display {
+            canvas,
+            texture_creator,
+            texture,
+        })
+    }
+
+    pub fn output_size(&self) -> Result<(u32, u32), EngineError> {
+        self.canvas.output_size().map_err(EngineError::Window)
+    }
+
+    pub fn present(&mut self, pixel_data: &[u8], canvas_width: u32) -> Result<(), EngineError> {
+        self.texture.update(None, pixel_data, canvas_width as usize * 4).map_err(|error| EngineError::Window(error.to_string()))?;
+        self.canvas.clear();
+        self.canvas.copy(&self.texture, None, None).map_err(EngineError::Window)?;
+        self.canvas.present();
+        Ok(())
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), EngineError> {
+        self.texture = self.texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGBA8888, width, height)
+            .map_err(|error| EngineError::Window(error.to_string()))?;
+        Ok(())
+    }
+}
@@ -1,49 +1,212 @@
 use async_std::task;
-use sdl2::{pixels::PixelFormatEnum, render::TextureCreator, video::WindowContext};
+use sdl2::{render::TextureCreator, video::{Window, WindowContext}};
 use wgpu::{util::DeviceExt, Buffer};
-use crate::types::{light::Light, pixel::Pixel, renderer::Renderer, uniforms::Uniforms, view_state::ViewState};
+use crate::types::{engine_error::EngineError, frame_stats::FrameStats, light::{Light, SHADOW_FACTOR}, overlay_image::OverlayImage, pick::Pick, pixel::Pixel};
+use crate::types::{render_settings::{DebugView, RenderSettings}, renderer::Renderer, uniforms::Uniforms, view_state::ViewState};
+use std::ops::Range;
+use super::{display::Display, operations::Operations, pixel_store::PixelStore, surface_display::SurfaceDisplay};
 
 pub struct GpuRenderer<'a> {
     device: wgpu::Device,
     queue: wgpu::Queue,
-    canvas: sdl2::render::Canvas<sdl2::video::Window>,
-    texture_creator: &'a TextureCreator<WindowContext>,
-    texture: sdl2::render::Texture<'a>,
+    display: Option<Display<'a>>,
+    surface_display: Option<SurfaceDisplay>,
     raytracing_compute_pipeline: wgpu::ComputePipeline,
     lighting_compute_pipeline: wgpu::ComputePipeline,
+    projection_bind_group_layout: wgpu::BindGroupLayout,
+    projection_depth_compute_pipeline: wgpu::ComputePipeline,
+    projection_resolve_compute_pipeline: wgpu::ComputePipeline,
     projection_compute_pipeline: wgpu::ComputePipeline,
-    pixels: Vec<Pixel>,
+    debug_bind_group_layout: wgpu::BindGroupLayout,
+    debug_range_compute_pipeline: wgpu::ComputePipeline,
+    debug_compute_pipeline: wgpu::ComputePipeline,
+    pixels: PixelStore,
+    pixel_buffer: Option<Buffer>,
+    shadow_buffer: Option<Buffer>,
+    pixel_capacity: usize,
+    frame_buffers: FrameBuffers,
     canvas_width: f32,
     canvas_height: f32,
-    batch_size: usize,
-    depth_map_buffer: Option<Buffer>,
+    max_workgroups: u32,
+    highlight_id: u32,
+    last_view_state: Option<ViewState>,
+    backend: String,
+    /// Present when the device supports timestamp queries and profiling is on.
+    profiler: Option<PassProfiler>,
+    profiling: bool,
+    pass_timings: Vec<(&'static str, f32)>,
+    settings: RenderSettings,
+    overlay: Option<OverlayBuffer>,
+    // Visible and culled counts written by the projection pass, and where they are read back from
+    stats_buffer: Buffer,
+    stats_readback: Buffer,
+    frame_stats: FrameStats,
+    // Nearest and farthest depths of the frame, for the depth views
+    depth_range_buffer: Buffer,
+}
+
+/// The overlay texels on the GPU, copied into the image buffer row by row after the passes.
+struct OverlayBuffer {
+    buffer: Buffer,
+    image: OverlayImage,
+}
+
+const PASS_NAMES: [&str; 5] = ["raytracing", "lighting", "projection depth", "projection resolve", "projection"];
+
+/// Timestamps written at the start and end of every compute pass, resolved and read back after the frame.
+struct PassProfiler {
+    query_set: wgpu::QuerySet,
+    resolve: Buffer,
+    readback: Buffer,
+    // Nanoseconds per timestamp tick
+    period: f32,
+}
+
+impl PassProfiler {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> PassProfiler {
+        let count = PASS_NAMES.len() as u32 * 2;
+        let size = count as u64 * std::mem::size_of::<u64>() as u64;
+        PassProfiler {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Pass Timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count,
+            }),
+            resolve: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Resolve Buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Readback Buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+        }
+    }
+}
+
+/// Screen sized buffers, kept between frames and cleared at the start of each one.
+struct FrameBuffers {
+    raytracing_depth: Buffer,
+    projection_depth: Buffer,
+    depth_map: Buffer,
+    winner: Buffer,
+    overdraw: Buffer,
+    img: Buffer,
+    staging: Buffer,
+}
+
+impl FrameBuffers {
+    fn new(device: &wgpu::Device, size: usize) -> FrameBuffers {
+        FrameBuffers {
+            raytracing_depth: create_depth_buffer(device, size),
+            projection_depth: create_depth_buffer(device, size),
+            depth_map: create_depth_map_buffer(device, size),
+            winner: create_winner_buffer(device, size),
+            overdraw: create_overdraw_buffer(device, size),
+            img: create_image_buffer(device, size),
+            staging: create_staging_buffer(device, size),
+        }
+    }
+}
+
+const NO_HIGHLIGHT: u32 = u32::MAX;
+
+/// The graphics APIs to look for adapters on and whether software rasterizers may be used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdapterOptions {
+    pub backends: wgpu::Backends,
+    pub allow_software: bool,
+}
+
+impl Default for AdapterOptions {
+    fn default() -> AdapterOptions {
+        AdapterOptions { backends: wgpu::Backends::VULKAN, allow_software: false }
+    }
+}
+
+/// Backend names as written in the config, only the ones built with the matching feature have adapters.
+const BACKEND_NAMES: [(wgpu::Backends, &str); 4] = [
+    (wgpu::Backends::VULKAN, "vulkan"),
+    (wgpu::Backends::GL, "gl"),
+    (wgpu::Backends::METAL, "metal"),
+    (wgpu::Backends::DX12, "dx12"),
+];
+
+impl AdapterOptions {
+    /// Reads a comma separated list like "vulkan,gl", or "all".
+    pub fn parse_backends(list: &str) -> Option<wgpu::Backends> {
+        let mut backends = wgpu::Backends::empty();
+        for name in list.split(',').map(str::trim) {
+            backends |= match name {
+                "all" => BACKEND_NAMES.iter().fold(wgpu::Backends::empty(), |all, (backend, _)| all | *backend),
+                _ => BACKEND_NAMES.iter().find(|(_, known)| *known == name)?.0,
+            };
+        }
+        Some(backends)
+    }
+
+    pub fn backend_list(&self) -> String {
+        BACKEND_NAMES.iter().filter(|(backend, _)| self.backends.contains(*backend)).map(|(_, name)| *name).collect::<Vec<_>>().join(",")
+    }
 }
 
 impl GpuRenderer<'_> {
     pub async fn new<'a>(
         canvas: sdl2::render::Canvas<sdl2::video::Window>,
         texture_creator: &'a TextureCreator<WindowContext>,
-    ) -> GpuRenderer<'a> {
-        let (canvas_width, canvas_height) = canvas.output_size().unwrap();
-        let texture = texture_creator
-            .create_texture_streaming(PixelFormatEnum::RGBA8888, canvas_width, canvas_height)
-            .unwrap();
+        options: &AdapterOptions,
+    ) -> Result<GpuRenderer<'a>, EngineError> {
+        let display = Display::new(canvas, texture_creator)?;
+        let (canvas_width, canvas_height) = display.output_size()?;
+        GpuRenderer::create(Some(display), None, canvas_width, canvas_height, options).await
+    }
 
+    /// Presents through a swapchain on the window instead of reading frames back into an SDL texture.
+    pub async fn with_surface(window: Window, vsync: bool, options: &AdapterOptions) -> Result<GpuRenderer<'static>, EngineError> {
+        let (canvas_width, canvas_height) = window.size();
+        GpuRenderer::create(None, Some((window, vsync)), canvas_width, canvas_height, options).await
+    }
+
+    /// Renders into the image buffer only, frames are read back with `draw`.
+    pub async fn headless(width: u32, height: u32, options: &AdapterOptions) -> Result<GpuRenderer<'static>, EngineError> {
+        GpuRenderer::create(None, None, width, height, options).await
+    }
+
+    async fn create<'a>(
+        display: Option<Display<'a>>,
+        window: Option<(Window, bool)>,
+        canvas_width: u32,
+        canvas_height: u32,
+        options: &AdapterOptions,
+    ) -> Result<GpuRenderer<'a>, EngineError> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN,
+            backends: options.backends,
             ..Default::default()
         });
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: None,
-                ..Default::default()
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = request_device(&adapter).await;
-        let batch_size = device.limits().max_compute_workgroups_per_dimension as usize;
+        let surface = match &window {
+            Some((window, _)) => Some(SurfaceDisplay::create_surface(&instance, window)?),
+            None => None,
+        };
+        let adapter = select_adapter(&instance, options, surface.as_ref())?;
+
+        let info = adapter.get_info();
+        let backend = format!("gpu{} ({:?}, {})", if window.is_some() { " surface" } else { "" }, info.backend, info.name);
+        let (device, queue) = request_device(&adapter).await?;
+        let surface_display = match surface.zip(window) {
+            Some((surface, (window, vsync))) => Some(SurfaceDisplay::new(surface, window, vsync, &adapter, &device)?),
+            None => None,
+        };
+        let max_workgroups = device.limits().max_compute_workgroups_per_dimension;
+        let profiler = device.features().contains(wgpu::Features::TIMESTAMP_QUERY).then(|| PassProfiler::new(&device, &queue));
+        let frame_buffers = FrameBuffers::new(&device, (canvas_width * canvas_height) as usize);
+        let stats_buffer = create_stats_buffer(&device);
+        let stats_readback = create_staging_buffer(&device, 2);
+        let depth_range_buffer = create_depth_range_buffer(&device);
 
         let shader_module = create_shader_module("Raytracing Compute Shader", &device, include_str!("raytracing_shader.wgsl"));
         let raytracing_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
@@ -65,44 +228,189 @@ impl GpuRenderer<'_> {
             cache: None,
         });
 
+        // The projection entry points share one layout so a single bind group serves all of them
+        let projection_bind_group_layout = create_projection_bind_group_layout(&device);
+        let projection_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Projection Pipeline Layout"),
+            bind_group_layouts: &[&projection_bind_group_layout],
+            push_constant_ranges: &[],
+        });
         let shader_module = create_shader_module("Projection Compute Shader", &device, include_str!("projection_shader.wgsl"));
+        let projection_compilation_options = wgpu::PipelineCompilationOptions {
+            constants: &[("SHADOW_FACTOR", SHADOW_FACTOR as f64)],
+            ..Default::default()
+        };
+        let projection_depth_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Projection Depth Compute Pipeline"),
+            layout: Some(&projection_pipeline_layout),
+            module: &shader_module,
+            entry_point: Some("depth"),
+            compilation_options: projection_compilation_options.clone(),
+            cache: None,
+        });
+        let projection_resolve_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Projection Resolve Compute Pipeline"),
+            layout: Some(&projection_pipeline_layout),
+            module: &shader_module,
+            entry_point: Some("resolve"),
+            compilation_options: projection_compilation_options.clone(),
+            cache: None,
+        });
         let projection_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
             label: Some("Projection Compute Pipeline"),
-            layout: None,
+            layout: Some(&projection_pipeline_layout),
+            module: &shader_module,
+            entry_point: Some("main"),
+            compilation_options: projection_compilation_options.clone(),
+            cache: None,
+        });
+
+        let debug_bind_group_layout = create_debug_bind_group_layout(&device);
+        let debug_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Pipeline Layout"),
+            bind_group_layouts: &[&debug_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader_module = create_shader_module("Debug Compute Shader", &device, include_str!("debug_shader.wgsl"));
+        let debug_range_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Debug Range Compute Pipeline"),
+            layout: Some(&debug_pipeline_layout),
+            module: &shader_module,
+            entry_point: Some("range"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let debug_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Debug Compute Pipeline"),
+            layout: Some(&debug_pipeline_layout),
             module: &shader_module,
             entry_point: Some("main"),
             compilation_options: Default::default(),
             cache: None,
         });
 
-        GpuRenderer {
+        Ok(GpuRenderer {
             device,
             queue,
-            canvas,
-            texture_creator,
-            texture,
+            display,
+            surface_display,
             raytracing_compute_pipeline,
             lighting_compute_pipeline,
+            projection_bind_group_layout,
+            projection_depth_compute_pipeline,
+            projection_resolve_compute_pipeline,
             projection_compute_pipeline,
-            pixels: Vec::new(),
+            debug_bind_group_layout,
+            debug_range_compute_pipeline,
+            debug_compute_pipeline,
+            pixels: PixelStore::new(),
+            pixel_buffer: None,
+            shadow_buffer: None,
+            pixel_capacity: 0,
+            frame_buffers,
             canvas_width: canvas_width as f32,
             canvas_height: canvas_height as f32,
-            batch_size,
-            depth_map_buffer: None
-        }
+            max_workgroups,
+            highlight_id: NO_HIGHLIGHT,
+            last_view_state: None,
+            backend,
+            profiler,
+            profiling: false,
+            pass_timings: Vec::new(),
+            settings: RenderSettings::default(),
+            overlay: None,
+            stats_buffer,
+            stats_readback,
+            frame_stats: FrameStats::default(),
+            depth_range_buffer,
+        })
     }
-}
 
-impl Renderer<'_> for GpuRenderer<'_> {
-    fn render(&mut self, view_state: &ViewState, light: &Light) {
+    /// Runs all passes and reads the finished frame back as RGBA8888 texels.
+    pub fn draw(&mut self, view_state: &ViewState, light: &Light) -> Result<Vec<u8>, EngineError> {
+        self.last_view_state = Some(*view_state);
         let buffer_size = (self.canvas_width * self.canvas_height) as usize;
-        let raytracing_depth_buffer = create_depth_buffer(&self.device, buffer_size);
-        let raytracing_depth_map_buffer = create_depth_map_buffer(&self.device, buffer_size);
-        let projection_depth_buffer = create_depth_buffer(&self.device, buffer_size);
-        let projection_depth_map_buffer = create_depth_map_buffer(&self.device, buffer_size);
-        let img_buffer = create_image_buffer(&self.device, buffer_size);
-        let staging_buffer = create_staging_buffer(&self.device, buffer_size);
-        let lock_buffer = create_lock_buffer(&self.device, buffer_size);
+        let frame = &self.frame_buffers;
+        let mut encoder = self.encode(view_state, light);
+
+        encoder.copy_buffer_to_buffer(&frame.img, 0, &frame.staging, 0, (std::mem::size_of::<u32>() * buffer_size) as u64);
+        self.queue.submit(Some(encoder.finish()));
+
+        let pixel_data = self.read_buffer(&frame.staging)?;
+        self.read_pass_timings()?;
+        self.read_frame_stats()?;
+        Ok(pixel_data)
+    }
+
+    /// Profiling only times frames that had pixels to draw, otherwise no pass ran.
+    fn timed_profiler(&self) -> Option<&PassProfiler> {
+        self.profiler.as_ref().filter(|_| self.profiling && !self.pixels.pixels().is_empty())
+    }
+
+    fn read_pass_timings(&mut self) -> Result<(), EngineError> {
+        let Some(profiler) = self.timed_profiler() else {
+            self.pass_timings.clear();
+            return Ok(());
+        };
+        let period = profiler.period;
+        let bytes = self.read_buffer(&profiler.readback)?;
+        let ticks: &[u64] = bytemuck::cast_slice(&bytes);
+        self.pass_timings = PASS_NAMES
+            .iter()
+            .zip(ticks.chunks_exact(2))
+            .skip(self.first_pass())
+            .map(|(name, pair)| (*name, pair[1].saturating_sub(pair[0]) as f32 * period / 1_000_000.0))
+            .collect();
+        Ok(())
+    }
+
+    fn read_frame_stats(&mut self) -> Result<(), EngineError> {
+        if !self.settings.stats {
+            return Ok(());
+        }
+        self.frame_stats = if self.pixels.pixels().is_empty() {
+            FrameStats::default()
+        } else {
+            let bytes = self.read_buffer(&self.stats_readback)?;
+            let counts: &[u32] = bytemuck::cast_slice(&bytes);
+            FrameStats { visible: counts[0] as usize, culled: counts[1] as usize }
+        };
+        Ok(())
+    }
+
+    /// The raytracing and lighting passes only run for shadows.
+    fn first_pass(&self) -> usize {
+        if self.settings.shadows { 0 } else { 2 }
+    }
+
+    /// Maps a MAP_READ buffer once the submitted work is done and copies its contents out.
+    fn read_buffer(&self, buffer: &Buffer) -> Result<Vec<u8>, EngineError> {
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = flume::bounded(1);
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| {
+            let _ = sender.send(v);
+        });
+        self.device.poll(wgpu::PollType::wait()).map_err(|error| EngineError::Gpu(format!("{:?}", error)))?;
+        task::block_on(async { 
+            match receiver.recv_async().await {
+                Ok(Ok(())) => {
+                    let data = buffer_slice.get_mapped_range();
+                    let pixel_data: Vec<u8> = bytemuck::cast_slice(&data).to_vec();
+
+                    drop(data);
+                    buffer.unmap();
+
+                    Ok(pixel_data)
+                },
+                Ok(Err(error)) => Err(EngineError::Gpu(format!("failed to read the frame back: {}", error))),
+                Err(_) => Err(EngineError::Gpu(String::from("the device was lost before the frame was read back"))),
+            }
+        })
+    }
+
+    /// Records every pass of a frame, leaving the result in the image buffer.
+    fn encode(&self, view_state: &ViewState, light: &Light) -> wgpu::CommandEncoder {
+        let frame = &self.frame_buffers;
 
         let uniforms = Uniforms {
             angle_x: view_state.angle_x,
@@ -128,155 +436,344 @@ impl Renderer<'_> for GpuRenderer<'_> {
             ref_y: view_state.ref_y,
             ref_z: view_state.ref_z,
             z_offset: view_state.z_offset,
+            highlight_id: self.highlight_id,
+            flags: self.settings.flags(),
+            debug_view: self.settings.debug_view as u32,
         };
         let uniform_buffer = create_uniform_buffer(&self.device, uniforms);
 
-        // TODO: iterate over objects here and for each render the pixels
-
-        let num_batches = (self.pixels.len() + self.batch_size - 1) / self.batch_size;
-        for batch_index in 0..num_batches {
-            let start = batch_index * self.batch_size;
-            let end = std::cmp::min(start + self.batch_size, self.pixels.len());
-            let pixel_batch = &self.pixels[start..end];
-            let pixel_buffer =
-                create_pixel_buffer(&self.device, pixel_batch.len() * std::mem::size_of::<Pixel>());
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Main Encoder"),
+        });
+        encoder.clear_buffer(&frame.raytracing_depth, 0, None);
+        encoder.clear_buffer(&frame.projection_depth, 0, None);
+        encoder.clear_buffer(&frame.depth_map, 0, None);
+        encoder.clear_buffer(&frame.winner, 0, None);
+        encoder.clear_buffer(&frame.img, 0, None);
 
-            self.queue.write_buffer(&pixel_buffer, 0, bytemuck::cast_slice(&pixel_batch));
+        // TODO: iterate over objects here and for each render the pixels
 
-            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Raytracing Encoder"),
+        let pixel_count = self.pixels.pixels().len();
+        if let (Some(pixel_buffer), Some(shadow_buffer), true) = (&self.pixel_buffer, &self.shadow_buffer, pixel_count > 0) {
+            encoder.clear_buffer(shadow_buffer, 0, None);
+            encoder.clear_buffer(&self.stats_buffer, 0, None);
+            if self.settings.debug_view == DebugView::Overdraw {
+                encoder.clear_buffer(&frame.overdraw, 0, None);
+            }
+            let workgroups = self.workgroups(pixel_count);
+            // Only the live part of the buffer is bound, so the shaders' arrayLength is the pixel count
+            let pixel_binding = wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: pixel_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(std::mem::size_of_val(self.pixels.pixels()) as u64),
             });
 
             let bind_group_layout = self.raytracing_compute_pipeline.get_bind_group_layout(0);
-            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            let raytracing_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
                 layout: &bind_group_layout,
                 entries: &[
                     wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 1, resource: pixel_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 2, resource: raytracing_depth_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 3, resource: raytracing_depth_map_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 4, resource: lock_buffer.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 1, resource: pixel_binding.clone(), },
+                    wgpu::BindGroupEntry { binding: 2, resource: frame.raytracing_depth.as_entire_binding(), },
                 ],
                 label: None,
             });
 
-            {
-                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Raytracing Compute Pass"),
-                    timestamp_writes: None,
-                });
-                cpass.set_pipeline(&self.raytracing_compute_pipeline);
-                cpass.set_bind_group(0, &bind_group, &[]);
-                cpass.dispatch_workgroups((end - start) as u32, 1, 1);
-            }
-
-            self.queue.submit(Some(encoder.finish()));
-        }
-
-        for batch_index in 0..num_batches {
-            let start = batch_index * self.batch_size;
-            let end = std::cmp::min(start + self.batch_size, self.pixels.len());
-            let pixel_batch = &self.pixels[start..end];
-            let pixel_buffer =
-                create_pixel_buffer(&self.device, pixel_batch.len() * std::mem::size_of::<Pixel>());
-
-            self.queue.write_buffer(&pixel_buffer, 0, bytemuck::cast_slice(&pixel_batch));
-            
-            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Main Encoder"),
-            });
-
             let bind_group_layout = self.lighting_compute_pipeline.get_bind_group_layout(0);
-            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            let lighting_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
                 layout: &bind_group_layout,
                 entries: &[
                     wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 1, resource: pixel_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 2, resource: raytracing_depth_buffer.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 1, resource: pixel_binding.clone(), },
+                    wgpu::BindGroupEntry { binding: 2, resource: frame.raytracing_depth.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 3, resource: shadow_buffer.as_entire_binding(), },
                 ],
                 label: None,
             });
 
-            {
-                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Lighting Compute Pass"),
-                    timestamp_writes: None,
-                });
-                cpass.set_pipeline(&self.lighting_compute_pipeline);
-                cpass.set_bind_group(0, &bind_group, &[]);
-                cpass.dispatch_workgroups((end - start) as u32, 1, 1);
-            }
-
-            let bind_group_layout = self.projection_compute_pipeline.get_bind_group_layout(0);
-            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &bind_group_layout,
+            let projection_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.projection_bind_group_layout,
                 entries: &[
                     wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 1, resource: pixel_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 2, resource: img_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 3, resource: projection_depth_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 4, resource: projection_depth_map_buffer.as_entire_binding(), },
-                    wgpu::BindGroupEntry { binding: 5, resource: lock_buffer.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 1, resource: pixel_binding.clone(), },
+                    wgpu::BindGroupEntry { binding: 2, resource: frame.img.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 3, resource: frame.projection_depth.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 4, resource: frame.depth_map.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 5, resource: frame.winner.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 6, resource: shadow_buffer.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 7, resource: self.stats_buffer.as_entire_binding(), },
+                    wgpu::BindGroupEntry { binding: 8, resource: frame.overdraw.as_entire_binding(), },
                 ],
                 label: None,
             });
 
-            {
+            // Each pass must see the complete result of the previous one, which separate compute passes guarantee
+            let profiler = self.timed_profiler();
+            let passes = [
+                ("Raytracing Compute Pass", &self.raytracing_compute_pipeline, &raytracing_bind_group),
+                ("Lighting Compute Pass", &self.lighting_compute_pipeline, &lighting_bind_group),
+                ("Projection Depth Compute Pass", &self.projection_depth_compute_pipeline, &projection_bind_group),
+                ("Projection Resolve Compute Pass", &self.projection_resolve_compute_pipeline, &projection_bind_group),
+                ("Projection Compute Pass", &self.projection_compute_pipeline, &projection_bind_group),
+            ];
+            for (index, (label, pipeline, bind_group)) in passes.into_iter().enumerate() {
+                // Skipped passes still write their timestamps when profiling, so every query gets resolved
+                let skipped = index < self.first_pass();
+                if skipped && profiler.is_none() {
+                    continue;
+                }
                 let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Projection Compute Pass"),
-                    timestamp_writes: None,
+                    label: Some(label),
+                    timestamp_writes: profiler.map(|profiler| wgpu::ComputePassTimestampWrites {
+                        query_set: &profiler.query_set,
+                        beginning_of_pass_write_index: Some(index as u32 * 2),
+                        end_of_pass_write_index: Some(index as u32 * 2 + 1),
+                    }),
                 });
-                cpass.set_pipeline(&self.projection_compute_pipeline);
-                cpass.set_bind_group(0, &bind_group, &[]);
-                cpass.dispatch_workgroups((end - start) as u32, 1, 1);
+                if !skipped {
+                    cpass.set_pipeline(pipeline);
+                    cpass.set_bind_group(0, bind_group, &[]);
+                    cpass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+                }
+            }
+            if let Some(profiler) = profiler {
+                let count = PASS_NAMES.len() as u32 * 2;
+                encoder.resolve_query_set(&profiler.query_set, 0..count, &profiler.resolve, 0);
+                encoder.copy_buffer_to_buffer(&profiler.resolve, 0, &profiler.readback, 0, profiler.resolve.size());
+            }
+            if self.settings.debug_view != DebugView::Shaded {
+                self.encode_debug_view(&mut encoder, &uniform_buffer, shadow_buffer);
             }
+            if self.settings.stats {
+                encoder.copy_buffer_to_buffer(&self.stats_buffer, 0, &self.stats_readback, 0, self.stats_buffer.size());
+            }
+        }
 
-            self.queue.submit(Some(encoder.finish()));
+        if let Some(overlay) = &self.overlay {
+            let (columns, rows) = overlay.image.visible_size(self.canvas_width as u32, self.canvas_height as u32);
+            let texel_size = std::mem::size_of::<u32>() as u64;
+            for row in (0..rows as u64).filter(|_| columns > 0) {
+                let source = row * overlay.image.width as u64 * texel_size;
+                let target = ((overlay.image.y as u64 + row) * self.canvas_width as u64 + overlay.image.x as u64) * texel_size;
+                encoder.copy_buffer_to_buffer(&overlay.buffer, source, &frame.img, target, columns as u64 * texel_size);
+            }
         }
 
-        self.depth_map_buffer = Some(projection_depth_map_buffer);
+        encoder
+    }
 
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Image Encoder"),
+    /// Draws the buffers the debug view shows over the shaded image, once the passes that fill them are done.
+    fn encode_debug_view(&self, encoder: &mut wgpu::CommandEncoder, uniform_buffer: &Buffer, shadow_buffer: &Buffer) {
+        let frame = &self.frame_buffers;
+        let debug_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.debug_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 1, resource: frame.img.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 2, resource: frame.projection_depth.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 3, resource: frame.depth_map.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 4, resource: frame.winner.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 5, resource: shadow_buffer.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 6, resource: frame.raytracing_depth.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 7, resource: frame.overdraw.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 8, resource: self.depth_range_buffer.as_entire_binding(), },
+            ],
+            label: None,
         });
 
-        encoder.copy_buffer_to_buffer(&img_buffer, 0, &staging_buffer, 0, (std::mem::size_of::<u32>() * buffer_size) as u64);
-        self.queue.submit(Some(encoder.finish()));
+        encoder.clear_buffer(&self.depth_range_buffer, 0, None);
+        // The debug passes run once per screen position rather than per pixel
+        let workgroups = self.workgroups((self.canvas_width * self.canvas_height) as usize);
+        for (label, pipeline) in [("Debug Range Compute Pass", &self.debug_range_compute_pipeline), ("Debug Compute Pass", &self.debug_compute_pipeline)] {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some(label),
+                timestamp_writes: None,
+            });
+            cpass.set_pipeline(pipeline);
+            cpass.set_bind_group(0, &debug_bind_group, &[]);
+            cpass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+        }
+    }
 
-        let buffer_slice = staging_buffer.slice(..);
-        let (sender, receiver) = flume::bounded(1);
-        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
-        let _ = self.device.poll(wgpu::PollType::wait());
-        let pixel_data= task::block_on(async { 
-            if let Ok(Ok(())) = receiver.recv_async().await {
-                let data = buffer_slice.get_mapped_range();
-                let pixel_data: Vec<u8> = bytemuck::cast_slice(&data).to_vec();
-
-                drop(data);
-                staging_buffer.unmap();
-
-                pixel_data
-            } else {
-                panic!("failed to run compute on gpu!")
-            }
-        });
+    /// Copies the changed part of the pixel store to the GPU, growing the buffers when they are full.
+    fn sync_pixels(&mut self, dirty: Range<usize>) {
+        let pixels = self.pixels.pixels();
+        if pixels.len() > self.pixel_capacity {
+            self.pixel_capacity = pixels.len().next_power_of_two();
+            let pixel_buffer = create_pixel_buffer(&self.device, self.pixel_capacity * std::mem::size_of::<Pixel>());
+            self.queue.write_buffer(&pixel_buffer, 0, bytemuck::cast_slice(pixels));
+            self.pixel_buffer = Some(pixel_buffer);
+            self.shadow_buffer = Some(create_shadow_buffer(&self.device, self.pixel_capacity));
+            return;
+        }
+
+        // Pixels past the end were removed, there is nothing left to copy for them
+        let dirty = dirty.start..dirty.end.min(pixels.len());
+        if let (Some(pixel_buffer), false) = (&self.pixel_buffer, dirty.is_empty()) {
+            let offset = (dirty.start * std::mem::size_of::<Pixel>()) as u64;
+            self.queue.write_buffer(pixel_buffer, offset, bytemuck::cast_slice(&pixels[dirty]));
+        }
+    }
 
-        self.texture.update(None, &pixel_data, self.canvas_width as usize * 4).unwrap();
-        self.canvas.clear();
-        self.canvas.copy(&self.texture, None, None).unwrap();
-        self.canvas.present();
+    /// Splits the 64 wide workgroups over two dimensions once they exceed the per dimension limit.
+    fn workgroups(&self, count: usize) -> (u32, u32) {
+        let groups = count.div_ceil(64) as u32;
+        let x = groups.min(self.max_workgroups);
+        (x, groups.div_ceil(x.max(1)))
+    }
+}
+
+impl Renderer<'_> for GpuRenderer<'_> {
+    fn render(&mut self, view_state: &ViewState, light: &Light) -> Result<(), EngineError> {
+        if let Some(surface_display) = &self.surface_display {
+            self.last_view_state = Some(*view_state);
+            let encoder = self.encode(view_state, light);
+            surface_display.present(&self.device, &self.queue, encoder, &self.frame_buffers.img);
+            self.read_pass_timings()?;
+            return self.read_frame_stats();
+        }
+
+        let pixel_data = self.draw(view_state, light)?;
+        if let Some(display) = self.display.as_mut() {
+            display.present(&pixel_data, self.canvas_width as u32)?;
+        }
+        Ok(())
     }
 
     fn load_pixels(&mut self, new_pixels: Vec<Pixel>) {
-        self.pixels.extend(new_pixels);
+        let dirty = self.pixels.load(new_pixels);
+        self.sync_pixels(dirty);
+    }
+
+    fn add_object(&mut self, id: u32, pixels: Vec<Pixel>) {
+        let dirty = self.pixels.add(id, pixels);
+        self.sync_pixels(dirty);
+    }
+
+    fn update_object(&mut self, id: u32, pixels: Vec<Pixel>) {
+        let dirty = self.pixels.update(id, pixels);
+        self.sync_pixels(dirty);
+    }
+
+    fn remove_object(&mut self, id: u32) {
+        if let Some(dirty) = self.pixels.remove(id) {
+            self.sync_pixels(dirty);
+        }
     }
-    
-    fn resize(&mut self, width: u32, height: u32) {
+
+    fn pick(&mut self, x: i32, y: i32) -> Option<Pick> {
+        let view_state = self.last_view_state?;
+        if x < 0 || y < 0 || x >= self.canvas_width as i32 || y >= self.canvas_height as i32 {
+            return None;
+        }
+
+        // The winner buffer holds the index + 1 of the pixel drawn at each position, 0 where nothing was
+        let offset = ((y * self.canvas_width as i32 + x) as usize * std::mem::size_of::<u32>()) as u64;
+        let staging_buffer = create_staging_buffer(&self.device, 2);
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pick Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&self.frame_buffers.winner, offset, &staging_buffer, 0, 4);
+        encoder.copy_buffer_to_buffer(&self.frame_buffers.projection_depth, offset, &staging_buffer, 4, 4);
+        self.queue.submit(Some(encoder.finish()));
+
+        let data = self.read_buffer(&staging_buffer).ok()?;
+        let values: &[u32] = bytemuck::cast_slice(&data);
+        let pixel_index = values[0].checked_sub(1)? as usize;
+        let pixel = self.pixels.pixels().get(pixel_index)?;
+
+        let (x, y, z) = Operations::rotate(
+            (pixel.x, pixel.y, pixel.z),
+            (view_state.angle_x, view_state.angle_y, view_state.angle_z)
+        );
+        Some(Pick {
+            object_id: pixel.id,
+            pixel_index,
+            x: x + view_state.ref_x,
+            y: y + view_state.ref_y,
+            z: z + view_state.ref_z,
+            depth: depth_from_key(values[1]),
+        })
+    }
+
+    fn set_highlight(&mut self, id: Option<u32>) {
+        self.highlight_id = id.unwrap_or(NO_HIGHLIGHT);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), EngineError> {
         self.canvas_width = width as f32;
         self.canvas_height = height as f32;
-        self.texture = self.texture_creator
-        .create_texture_streaming(PixelFormatEnum::RGBA8888, width, height)
-        .unwrap();
+        self.frame_buffers = FrameBuffers::new(&self.device, (width * height) as usize);
+        if let Some(display) = self.display.as_mut() {
+            display.resize(width, height)?;
+        }
+        if let Some(surface_display) = self.surface_display.as_mut() {
+            surface_display.resize(&self.device, width, height);
+        }
+        Ok(())
+    }
+
+    fn screenshot(&mut self) -> Result<(Vec<u8>, u32, u32), EngineError> {
+        // The image buffer still holds the last frame, whether it was read back or presented to the surface
+        let frame = &self.frame_buffers;
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Screenshot Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&frame.img, 0, &frame.staging, 0, frame.img.size());
+        self.queue.submit(Some(encoder.finish()));
+        Ok((self.read_buffer(&frame.staging)?, self.canvas_width as u32, self.canvas_height as u32))
+    }
+
+    fn backend(&self) -> String {
+        self.backend.clone()
+    }
+
+    fn pixel_count(&self) -> usize {
+        self.pixels.pixels().len()
+    }
+
+    fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+    }
+
+    fn pass_timings(&self) -> Vec<(&'static str, f32)> {
+        self.pass_timings.clone()
+    }
+
+    fn set_render_settings(&mut self, settings: RenderSettings) {
+        self.settings = settings;
+    }
+
+    fn set_overlay(&mut self, overlay: Option<OverlayImage>) {
+        let Some(image) = overlay.filter(|image| !image.texels.is_empty()) else {
+            self.overlay = None;
+            return;
+        };
+        let size = std::mem::size_of_val(image.texels.as_slice()) as u64;
+        let buffer = match self.overlay.take() {
+            Some(current) if current.buffer.size() == size => current.buffer,
+            _ => self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Overlay Buffer"),
+                size,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        };
+        self.queue.write_buffer(&buffer, 0, bytemuck::cast_slice(&image.texels));
+        self.overlay = Some(OverlayBuffer { buffer, image });
+    }
+
+    fn frame_stats(&self) -> Option<FrameStats> {
+        self.settings.stats.then_some(self.frame_stats)
+    }
+}
+
+/// Inverse of the shaders' `depth_key`.
+fn depth_from_key(key: u32) -> f32 {
+    if key & 0x8000_0000 != 0 {
+        f32::from_bits(key & 0x7fff_ffff)
+    } else {
+        f32::from_bits(!key)
     }
 }
 
@@ -297,11 +794,20 @@ fn create_pixel_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
     })
 }
 
+fn create_shadow_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Shadow Buffer"),
+        size: (std::mem::size_of::<u32>() * size) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
 fn create_depth_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
     device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Depth Buffer"),
-        size: (std::mem::size_of::<f32>() * size) as u64,
-        usage: wgpu::BufferUsages::STORAGE,
+        size: (std::mem::size_of::<u32>() * size) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     })
 }
@@ -310,7 +816,7 @@ fn create_depth_map_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
     device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Depth Map Buffer"),
         size: (std::mem::size_of::<u32>() * size) as u64,
-        usage: wgpu::BufferUsages::STORAGE,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     })
 }
@@ -319,38 +825,165 @@ fn create_image_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
     device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Image Buffer"),
         size: (std::mem::size_of::<u32>() * size) as u64,
-        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     })
 }
 
 fn create_staging_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
     device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Image Buffer"),
+        label: Some("Staging Buffer"),
         size: (std::mem::size_of::<u32>() * size) as u64,
         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     })
 }
 
-fn create_lock_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
+fn create_stats_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Stats Buffer"),
+        size: (std::mem::size_of::<u32>() * 2) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_depth_range_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Depth Range Buffer"),
+        size: (std::mem::size_of::<u32>() * 4) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_overdraw_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Overdraw Buffer"),
+        size: (std::mem::size_of::<u32>() * size) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_winner_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
     device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Lock Buffer"),
+        label: Some("Winner Buffer"),
         size: (std::mem::size_of::<u32>() * size) as u64,
-        usage: wgpu::BufferUsages::STORAGE,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     })
 }
 
-async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+fn uniform_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn storage_entry(binding: u32, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn create_projection_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Projection Bind Group Layout"),
+        entries: &[
+            uniform_entry(0),
+            storage_entry(1, true),
+            storage_entry(2, false),
+            storage_entry(3, false),
+            storage_entry(4, false),
+            storage_entry(5, false),
+            storage_entry(6, true),
+            storage_entry(7, false),
+            storage_entry(8, false),
+        ],
+    })
+}
+
+/// The range and color entry points of the debug shader share one layout, like the projection ones.
+fn create_debug_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Debug Bind Group Layout"),
+        entries: &[
+            uniform_entry(0),
+            storage_entry(1, false),
+            storage_entry(2, true),
+            storage_entry(3, true),
+            storage_entry(4, true),
+            storage_entry(5, true),
+            storage_entry(6, true),
+            storage_entry(7, true),
+            storage_entry(8, false),
+        ],
+    })
+}
+
+/// Picks a discrete GPU over an integrated one over anything else, leaving out adapters that
+/// cannot run the compute shaders or present to the window. The error says why each one was passed over.
+fn select_adapter(instance: &wgpu::Instance, options: &AdapterOptions, surface: Option<&wgpu::Surface>) -> Result<wgpu::Adapter, EngineError> {
+    let mut usable = Vec::new();
+    let mut rejected = Vec::new();
+    for adapter in instance.enumerate_adapters(options.backends) {
+        let info = adapter.get_info();
+        let reason = if info.device_type == wgpu::DeviceType::Cpu && !options.allow_software {
+            Some("software adapter, allowed with renderer.software = true")
+        } else if !adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+            Some("no compute shaders")
+        } else if surface.is_some_and(|surface| !adapter.is_surface_supported(surface)) {
+            Some("cannot present to the window")
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => rejected.push(format!("{} ({:?}): {}", info.name, info.backend, reason)),
+            None => usable.push(adapter),
+        }
+    }
+
+    usable.sort_by_key(|adapter| match adapter.get_info().device_type {
+        wgpu::DeviceType::DiscreteGpu => 0,
+        wgpu::DeviceType::IntegratedGpu => 1,
+        wgpu::DeviceType::VirtualGpu => 2,
+        wgpu::DeviceType::Other => 3,
+        wgpu::DeviceType::Cpu => 4,
+    });
+    usable.into_iter().next().ok_or_else(|| match rejected.is_empty() {
+        true => EngineError::Gpu(format!("no adapter found for {}", options.backend_list())),
+        false => EngineError::Gpu(format!("no suitable adapter: {}", rejected.join("; "))),
+    })
+}
+
+async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue), EngineError> {
     adapter
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::default(),
+                // Timestamps are only used for profiling, adapters without them still render
+                required_features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+                // The whole point cloud is bound as one storage buffer
                 required_limits: wgpu::Limits {
                     max_texture_dimension_1d: 4096,
                     max_texture_dimension_2d: 4096,
+                    max_storage_buffer_binding_size: adapter.limits().max_storage_buffer_binding_size,
+                    max_buffer_size: adapter.limits().max_buffer_size,
                     ..wgpu::Limits::default()
                 },
                 memory_hints: wgpu::MemoryHints::Performance,
@@ -358,7 +991,7 @@ async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue)
             },
         )
         .await
-        .unwrap()
+        .map_err(|error| EngineError::Gpu(format!("could not open the device: {}", error)))
 }
 
 fn create_shader_module(label: &str, device: &wgpu::Device, source: &str) -> wgpu::ShaderModule {
@@ -367,3 +1000,70 @@ fn create_shader_module(label: &str, device: &wgpu::Device, source: &str) -> wgp
         source: wgpu::ShaderSource::Wgsl(source.into()),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::generator_helper::{Color, Generator};
+    use crate::helpers::test_helper::{test_light, test_view_state};
+
+    fn cube() -> Vec<Pixel> {
+        let generator = Generator { color: Color::checker([1.0, 0.2, 0.2, 1.0], [0.2, 0.4, 1.0, 1.0], 4), ..Generator::new(1, 6.0) };
+        generator.cuboid((23.0, 23.0, 23.0))
+    }
+
+    #[test]
+    fn repeated_frames_are_identical() {
+        let Ok(mut renderer) = task::block_on(GpuRenderer::headless(320, 240, &AdapterOptions::default())) else {
+            eprintln!("no gpu adapter available, skipping");
+            return;
+        };
+
+        // A second cube on the exact same positions makes every splat a depth tie
+        let pixels = cube();
+        let twin: Vec<Pixel> = pixels.iter().map(|pixel| Pixel {
+            id: 2,
+            r: 1.0 - pixel.r,
+            g: 1.0 - pixel.g,
+            b: 1.0 - pixel.b,
+            ..*pixel
+        }).collect();
+        renderer.load_pixels(pixels);
+        renderer.load_pixels(twin);
+
+        let view_state = test_view_state();
+        let light = test_light();
+
+        let first = renderer.draw(&view_state, &light).unwrap();
+        assert!(first.iter().any(|&byte| byte != 0));
+        for _ in 0..10 {
+            assert!(renderer.draw(&view_state, &light).unwrap() == first);
+        }
+    }
+
+    #[test]
+    fn overlay_covers_the_frame_and_pixels_are_counted() {
+        let options = AdapterOptions { backends: wgpu::Backends::all(), allow_software: true };
+        let Ok(mut renderer) = task::block_on(GpuRenderer::headless(320, 240, &options)) else {
+            eprintln!("no gpu adapter available, skipping");
+            return;
+        };
+        let pixels = cube();
+        let count = pixels.len();
+        renderer.load_pixels(pixels);
+        renderer.set_render_settings(RenderSettings { stats: true, ..RenderSettings::default() });
+        let overlay = OverlayImage { x: 300, y: 2, width: 30, height: 3, texels: (0..90).collect() };
+        renderer.set_overlay(Some(overlay.clone()));
+
+        let view_state = test_view_state();
+        let frame = renderer.draw(&view_state, &test_light()).unwrap();
+        let texels: &[u32] = bytemuck::cast_slice(&frame);
+        // Only the 20 columns left of the frame's edge are drawn
+        for row in 0..3 {
+            assert_eq!(&texels[(2 + row) * 320 + 300..][..20], &overlay.texels[row * 30..][..20]);
+        }
+
+        let stats = renderer.frame_stats().unwrap();
+        assert!(stats.visible > 0 && stats.visible + stats.culled <= count, "{:?}", stats);
+    }
+}
@@ -34,11 +34,18 @@ struct Uniforms {
     ref_y: f32,
     ref_z: f32,
     z_offset: f32,
+    highlight_id: u32,
+    flags: u32,
+    debug_view: u32,
 };
 
+// Gizmos and other overlays are drawn on top of the scene, unlit
+const OVERLAY_ID: u32 = 0xfffffffeu;
+
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
-@group(0) @binding(1) var<storage, read_write> pixels: array<Pixel>;
-@group(0) @binding(2) var<storage, read_write> depth_buffer: array<f32>;
+@group(0) @binding(1) var<storage, read> pixels: array<Pixel>;
+@group(0) @binding(2) var<storage, read> depth_buffer: array<u32>;
+@group(0) @binding(3) var<storage, read_write> shadow: array<u32>;
 
 fn rotate(v: vec3<f32>, angle: vec3<f32>) -> vec3<f32> {
     let cos_x = cos(angle.x);
@@ -62,6 +69,13 @@ fn rotate(v: vec3<f32>, angle: vec3<f32>) -> vec3<f32> {
     return vec3<f32>(final_x, final_y, rotated_y.z);
 }
 
+fn depth_from_key(key: u32) -> f32 {
+    if ((key & 0x80000000u) != 0u) {
+        return bitcast<f32>(key & 0x7fffffffu);
+    }
+    return bitcast<f32>(~key);
+}
+
 fn project(v: vec3<f32>, scale_factor: f32) -> vec2<i32> {
     return vec2<i32>(
         i32(v.x * scale_factor + uniforms.canvas_width / 2.0),
@@ -69,10 +83,16 @@ fn project(v: vec3<f32>, scale_factor: f32) -> vec2<i32> {
     );
 }
 
-@compute @workgroup_size(8, 8)
-fn main(@builtin(global_invocation_id) id: vec3<u32>) {
-    let index = id.x;
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = id.x + id.y * groups.x * 64u;
+    if (index >= arrayLength(&pixels)) {
+        return;
+    }
     let pixel = pixels[index];
+    if (pixel.id == OVERLAY_ID) {
+        return;
+    }
 
     var trasnformed_pixel = rotate(
         vec3<f32>(pixel.x, pixel.y, pixel.z),
@@ -107,13 +127,14 @@ fn main(@builtin(global_invocation_id) id: vec3<u32>) {
             in_bounds = true;
 
             let depth_index = py_offset * canvas_width + px_offset;
-            if (abs(depth_buffer[depth_index] - positioned_pixel.z) < 4.0) {
+            let key = depth_buffer[depth_index];
+            if (key != 0u && abs(depth_from_key(key) - positioned_pixel.z) < 4.0) {
                 return;
             }
         }
     }
     
     if (in_bounds) {
-        pixels[index].a = -1.0;
+        shadow[index] = 1u;
     }
 }
@@ -1,3 +1,8 @@
 pub mod gpu_renderer;
 pub mod cpu_renderer;
 pub mod operations;
+pub mod display;
+pub mod surface_display;
+pub mod pixel_store;
+pub mod debug_overlay;
+pub mod debug_views;
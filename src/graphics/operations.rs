@@ -26,6 +26,16 @@ impl Operations {
         (final_x, final_y, rotated_y.2)
     }
     
+    /// Undoes `rotate` with the same angles by applying the inverse rotations in reverse order.
+    pub fn rotate_inverse(v: (f32, f32, f32), angle: (f32, f32, f32)) -> (f32, f32, f32) {
+        let (x, y, z) = v;
+        let (angle_x, angle_y, angle_z) = angle;
+
+        let (x, y, z) = Operations::rotate((x, y, z), (0.0, 0.0, -angle_z));
+        let (x, y, z) = Operations::rotate((x, y, z), (0.0, -angle_y, 0.0));
+        Operations::rotate((x, y, z), (-angle_x, 0.0, 0.0))
+    }
+
     pub fn project(v: (f32, f32, f32), scale_factor: f32, canvas_width: f32, canvas_height: f32) -> (i32, i32) {
         let (x, y, _) = v;
     
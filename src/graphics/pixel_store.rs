@@ -0,0 +1,145 @@
+use std::ops::Range;
+use crate::types::pixel::Pixel;
+
+/// Pixels of all objects in one array, each object kept contiguous so it can be changed in place.
+/// Every change returns the range of the array that differs afterwards, which is what has to be
+/// copied to the GPU.
+pub struct PixelStore {
+    pixels: Vec<Pixel>,
+    objects: Vec<(u32, Range<usize>)>,
+}
+
+impl PixelStore {
+    pub fn new() -> PixelStore {
+        PixelStore {
+            pixels: Vec::new(),
+            objects: Vec::new(),
+        }
+    }
+
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    /// Adds pixels that carry their object ids already, grouping consecutive pixels of one object.
+    pub fn load(&mut self, new_pixels: Vec<Pixel>) -> Range<usize> {
+        let mut dirty = self.pixels.len()..self.pixels.len();
+        for group in new_pixels.chunk_by(|a, b| a.id == b.id) {
+            let range = self.add(group[0].id, group.to_vec());
+            dirty = dirty.start.min(range.start)..dirty.end.max(range.end);
+        }
+        dirty
+    }
+
+    /// Appends pixels to the object, creating it when it does not exist yet.
+    pub fn add(&mut self, id: u32, new_pixels: Vec<Pixel>) -> Range<usize> {
+        let new_pixels = with_id(id, new_pixels);
+        match self.position(id) {
+            Some(position) => {
+                let end = self.objects[position].1.end;
+                let count = new_pixels.len();
+                self.pixels.splice(end..end, new_pixels);
+                self.objects[position].1.end += count;
+                self.shift(position + 1, count as isize);
+                end..self.pixels.len()
+            }
+            None => {
+                let start = self.pixels.len();
+                self.pixels.extend(new_pixels);
+                self.objects.push((id, start..self.pixels.len()));
+                start..self.pixels.len()
+            }
+        }
+    }
+
+    /// Replaces all pixels of the object, creating it when it does not exist yet.
+    pub fn update(&mut self, id: u32, new_pixels: Vec<Pixel>) -> Range<usize> {
+        let Some(position) = self.position(id) else {
+            return self.add(id, new_pixels);
+        };
+
+        let range = self.objects[position].1.clone();
+        let new_pixels = with_id(id, new_pixels);
+        let count = new_pixels.len();
+        let old_len = self.pixels.len();
+        self.pixels.splice(range.clone(), new_pixels);
+        self.objects[position].1.end = range.start + count;
+        self.shift(position + 1, count as isize - range.len() as isize);
+
+        if count == range.len() {
+            range
+        } else {
+            range.start..old_len.max(self.pixels.len())
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Range<usize>> {
+        let position = self.position(id)?;
+        let (_, range) = self.objects.remove(position);
+        let old_len = self.pixels.len();
+        self.pixels.drain(range.clone());
+        self.shift(position, -(range.len() as isize));
+        Some(range.start..old_len)
+    }
+
+    fn position(&self, id: u32) -> Option<usize> {
+        self.objects.iter().position(|(object_id, _)| *object_id == id)
+    }
+
+    fn shift(&mut self, from: usize, by: isize) {
+        for (_, range) in self.objects[from..].iter_mut() {
+            range.start = range.start.wrapping_add_signed(by);
+            range.end = range.end.wrapping_add_signed(by);
+        }
+    }
+}
+
+fn with_id(id: u32, mut pixels: Vec<Pixel>) -> Vec<Pixel> {
+    for pixel in pixels.iter_mut() {
+        pixel.id = id;
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    fn pixels(count: usize, x: f32) -> Vec<Pixel> {
+        vec![Pixel { x, ..Pixel::zeroed() }; count]
+    }
+
+    #[test]
+    fn changes_shift_the_objects_after_them_and_report_what_moved() {
+        let mut store = PixelStore::new();
+        assert_eq!(store.add(1, pixels(2, 1.0)), 0..2);
+        assert_eq!(store.add(2, pixels(3, 2.0)), 2..5);
+        assert_eq!(store.add(3, pixels(4, 3.0)), 5..9);
+
+        // Same size only touches the object, growing or shrinking also moves everything after it
+        assert_eq!(store.update(2, pixels(3, 4.0)), 2..5);
+        assert_eq!(store.update(2, pixels(5, 5.0)), 2..11);
+        assert_eq!(store.objects, [(1, 0..2), (2, 2..7), (3, 7..11)]);
+        assert_eq!(store.update(2, pixels(1, 6.0)), 2..11);
+        assert_eq!(store.objects, [(1, 0..2), (2, 2..3), (3, 3..7)]);
+
+        // Appending to an object in the middle moves the ones after it too
+        assert_eq!(store.add(1, pixels(1, 7.0)), 2..8);
+        assert_eq!(store.objects, [(1, 0..3), (2, 3..4), (3, 4..8)]);
+
+        // The dirty span runs to the old end, so the stale tail gets cleared too
+        assert_eq!(store.remove(2), Some(3..8));
+        assert_eq!(store.remove(2), None);
+        assert_eq!(store.objects, [(1, 0..3), (3, 3..7)]);
+        let xs: Vec<f32> = store.pixels().iter().map(|pixel| pixel.x).collect();
+        assert_eq!(xs, [1.0, 1.0, 7.0, 3.0, 3.0, 3.0, 3.0]);
+        assert!(store.pixels()[..3].iter().all(|pixel| pixel.id == 1) && store.pixels()[3..].iter().all(|pixel| pixel.id == 3));
+
+        // Loading groups consecutive pixels by the id they carry
+        let mut loaded = pixels(2, 8.0);
+        loaded[1].id = 9;
+        assert_eq!(store.load(loaded), 7..9);
+        assert_eq!(store.objects, [(1, 0..3), (3, 3..7), (0, 7..8), (9, 8..9)]);
+    }
+}
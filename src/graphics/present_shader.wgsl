@@ -0,0 +1,31 @@
+@group(0) @binding(0) var<storage, read> img: array<u32>;
+@group(0) @binding(1) var output: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(2) var frame: texture_2d<f32>;
+
+@compute @workgroup_size(8, 8)
+fn to_texture(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let texel = img[id.y * size.x + id.x];
+    let color = vec4<f32>(
+        f32((texel >> 24u) & 0xffu),
+        f32((texel >> 16u) & 0xffu),
+        f32((texel >> 8u) & 0xffu),
+        255.0) / 255.0;
+    textureStore(output, id.xy, color);
+}
+
+// A single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(frame, vec2<i32>(position.xy), 0);
+}
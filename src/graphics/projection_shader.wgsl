@@ -34,14 +34,50 @@ struct Uniforms {
     ref_y: f32,
     ref_z: f32,
     z_offset: f32,
+    highlight_id: u32,
+    flags: u32,
+    debug_view: u32,
 };
 
+struct Splat {
+    visible: bool,
+    projected: vec2<i32>,
+    block_size: i32,
+    depth: f32,
+    transformed: vec3<f32>,
+};
+
+// Gizmos and other overlays are drawn on top of the scene, unlit
+const OVERLAY_ID: u32 = 0xfffffffeu;
+
+// Set from SHADOW_FACTOR when the pipelines are created
+override SHADOW_FACTOR: f32;
+
+// Bits of uniforms.flags, as in RenderSettings::flags
+const FLAG_LIGHTING: u32 = 2u;
+const FLAG_STATS: u32 = 4u;
+
+const DEBUG_OVERDRAW: u32 = 6u;
+
+// What became of a pixel in the color pass
+const HIDDEN: u32 = 0u;
+const DRAWN: u32 = 1u;
+const CULLED: u32 = 2u;
+
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
-@group(0) @binding(1) var<storage, read_write> pixels: array<Pixel>;
+@group(0) @binding(1) var<storage, read> pixels: array<Pixel>;
 @group(0) @binding(2) var<storage, read_write> img: array<u32>;
-@group(0) @binding(3) var<storage, read_write> depth_buffer: array<f32>;
+@group(0) @binding(3) var<storage, read_write> depth_buffer: array<atomic<u32>>;
 @group(0) @binding(4) var<storage, read_write> depth_map_buffer: array<u32>;
-@group(0) @binding(5) var<storage, read_write> lock: array<atomic<u32>>;
+@group(0) @binding(5) var<storage, read_write> winner_buffer: array<atomic<u32>>;
+@group(0) @binding(6) var<storage, read> shadow: array<u32>;
+// Visible and culled pixel counts
+@group(0) @binding(7) var<storage, read_write> stats: array<atomic<u32>>;
+// Splats drawn on each screen position, counted for the overdraw view
+@group(0) @binding(8) var<storage, read_write> overdraw: array<atomic<u32>>;
+
+var<workgroup> visible_count: atomic<u32>;
+var<workgroup> culled_count: atomic<u32>;
 
 fn rotate(v: vec3<f32>, angle: vec3<f32>) -> vec3<f32> {
     let cos_x = cos(angle.x);
@@ -68,16 +104,33 @@ fn rotate(v: vec3<f32>, angle: vec3<f32>) -> vec3<f32> {
 fn apply_lighting(
     position: vec3<f32>,
     light: vec3<f32>,
-    color: vec4<f32>
+    color: vec4<f32>,
+    shadowed: bool
 ) -> vec3<f32> {
-    if (color[3] == -1.0) {
-        return vec3<f32>(color[0], color[1], color[2]) * 0.3;
+    if (shadowed) {
+        return vec3<f32>(color[0], color[1], color[2]) * SHADOW_FACTOR;
     }
     let distance = distance(light, vec3<f32>(position.x, position.y, position.z));
     let intensity = uniforms.intensity / distance;
     return clamp(vec3<f32>(color[0], color[1], color[2]) * intensity, vec3<f32>(0.0), vec3<f32>(1.0));
 }
 
+// Maps a depth to a key whose unsigned order matches the float order, 0 is left for empty
+fn depth_key(z: f32) -> u32 {
+    let bits = bitcast<u32>(z);
+    if ((bits & 0x80000000u) != 0u) {
+        return ~bits;
+    }
+    return bits | 0x80000000u;
+}
+
+fn splat_key(pixel: Pixel, s: Splat) -> u32 {
+    if (pixel.id == OVERLAY_ID) {
+        return 0xffffffffu;
+    }
+    return depth_key(s.depth);
+}
+
 fn project(v: vec3<f32>, scale_factor: f32) -> vec2<i32> {
     return vec2<i32>(
         i32(v.x * scale_factor + uniforms.canvas_width / 2.0),
@@ -85,10 +138,8 @@ fn project(v: vec3<f32>, scale_factor: f32) -> vec2<i32> {
     );
 }
 
-@compute @workgroup_size(8, 8)
-fn main(@builtin(global_invocation_id) id: vec3<u32>) {
-    let index = id.x;
-    let pixel = pixels[index];
+fn splat(pixel: Pixel) -> Splat {
+    var result: Splat;
 
     var trasnformed_pixel = rotate(
         vec3<f32>(pixel.x, pixel.y, pixel.z),
@@ -101,49 +152,161 @@ fn main(@builtin(global_invocation_id) id: vec3<u32>) {
     positioned_pixel += vec3<f32>(uniforms.camera_x, uniforms.camera_y, uniforms.camera_z);
 
     if (uniforms.camera_z - positioned_pixel.z < uniforms.z_offset) {
-        return;
+        result.visible = false;
+        return result;
     }
 
     let scale_factor = uniforms.scale / (uniforms.camera_z - positioned_pixel.z);
-    let projected = project(positioned_pixel, scale_factor);
 
-    let lit_color = apply_lighting(
-        trasnformed_pixel,
-        vec3<f32>(uniforms.light_x, uniforms.light_y, uniforms.light_z),
-        vec4<f32>(pixel.r, pixel.g, pixel.b, pixel.a));
-    let color = vec4<f32>(lit_color, 1.0);
+    result.visible = true;
+    result.projected = project(positioned_pixel, scale_factor);
+    result.block_size = i32(ceil(scale_factor * pixel.size_factor));
+    result.depth = positioned_pixel.z;
+    result.transformed = trasnformed_pixel;
+    return result;
+}
 
+fn depth_index(s: Splat, dx: i32, dy: i32) -> i32 {
     let canvas_width = i32(uniforms.canvas_width);
     let canvas_height = i32(uniforms.canvas_height);
-    let block_size = i32(ceil(scale_factor * pixel.size_factor));
-    for (var dx: i32 = 0; dx < block_size; dx++) {
-        for (var dy: i32 = 0; dy < block_size; dy++) {
-            let px_offset = projected.x + dx;
-            let py_offset = projected.y + dy;
+    let px_offset = s.projected.x + dx;
+    let py_offset = s.projected.y + dy;
+
+    if (px_offset < 0 || px_offset >= canvas_width || py_offset < 0 || py_offset >= canvas_height) {
+        return -1;
+    }
+    return py_offset * canvas_width + px_offset;
+}
+
+// Visibility pass: keeps the nearest depth of every screen position
+@compute @workgroup_size(64)
+fn depth(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = id.x + id.y * groups.x * 64u;
+    if (index >= arrayLength(&pixels)) {
+        return;
+    }
+    let s = splat(pixels[index]);
+    if (!s.visible) {
+        return;
+    }
+
+    let key = splat_key(pixels[index], s);
+    for (var dx: i32 = 0; dx < s.block_size; dx++) {
+        for (var dy: i32 = 0; dy < s.block_size; dy++) {
+            let i = depth_index(s, dx, dy);
+            if (i >= 0) {
+                atomicMax(&depth_buffer[i], key);
+                if (uniforms.debug_view == DEBUG_OVERDRAW) {
+                    atomicAdd(&overdraw[i], 1u);
+                }
+            }
+        }
+    }
+}
+
+// Resolve pass: among the splats at the nearest depth the highest pixel index wins
+@compute @workgroup_size(64)
+fn resolve(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = id.x + id.y * groups.x * 64u;
+    if (index >= arrayLength(&pixels)) {
+        return;
+    }
+    let s = splat(pixels[index]);
+    if (!s.visible) {
+        return;
+    }
+
+    let key = splat_key(pixels[index], s);
+    for (var dx: i32 = 0; dx < s.block_size; dx++) {
+        for (var dy: i32 = 0; dy < s.block_size; dy++) {
+            let i = depth_index(s, dx, dy);
+            if (i >= 0 && atomicLoad(&depth_buffer[i]) == key) {
+                atomicMax(&winner_buffer[i], index + 1u);
+            }
+        }
+    }
+}
+
+fn pixel_color(pixel: Pixel, s: Splat, index: u32) -> vec4<f32> {
+    if (pixel.id == OVERLAY_ID) {
+        return vec4<f32>(pixel.r, pixel.g, pixel.b, 1.0);
+    }
+
+    let shadowed = shadow[index] != 0u;
+    var color = vec3<f32>(pixel.r, pixel.g, pixel.b);
+    if ((uniforms.flags & FLAG_LIGHTING) != 0u) {
+        color = apply_lighting(
+            s.transformed,
+            vec3<f32>(uniforms.light_x, uniforms.light_y, uniforms.light_z),
+            vec4<f32>(pixel.r, pixel.g, pixel.b, pixel.a),
+            shadowed);
+    } else if (shadowed) {
+        color *= SHADOW_FACTOR;
+    }
+    if (pixel.id == uniforms.highlight_id) {
+        color = mix(color, vec3<f32>(1.0, 0.8, 0.2), 0.5);
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+// Writes the pixel's color where it won the resolve
+fn shade(index: u32) -> u32 {
+    let pixel = pixels[index];
+    let s = splat(pixel);
+    if (!s.visible) {
+        return CULLED;
+    }
 
-            if (px_offset < 0 || px_offset >= canvas_width || py_offset < 0 || py_offset >= canvas_height) {
+    let color = pixel_color(pixel, s, index);
+    let winner = index + 1u;
+    var result = CULLED;
+    for (var dx: i32 = 0; dx < s.block_size; dx++) {
+        for (var dy: i32 = 0; dy < s.block_size; dy++) {
+            let i = depth_index(s, dx, dy);
+            if (i < 0) {
                 continue;
             }
+            if (result == CULLED) {
+                result = HIDDEN;
+            }
+            if (atomicLoad(&winner_buffer[i]) == winner) {
+                depth_map_buffer[i] = pixel.id;
 
-            let depth_index = py_offset * canvas_width + px_offset;
-            while (true) {
-                if (atomicCompareExchangeWeak(&lock[depth_index], 0u, 1u).exchanged) {
-                    if (positioned_pixel.z > depth_buffer[depth_index] || depth_map_buffer[depth_index] == 0u) {
-                        depth_buffer[depth_index] = positioned_pixel.z;
-                        depth_map_buffer[depth_index] = pixel.id;
-                        
-                        img[depth_index] = u32(color[0] * 255.0) << 24 |
-                                           u32(color[1] * 255.0) << 16 |
-                                           u32(color[2] * 255.0) << 8  |
-                                           u32(color[3] * 255.0);
-                    }
-                    atomicStore(&lock[depth_index], 0u);
-                    break;
-                }
-                if (positioned_pixel.z <= depth_buffer[depth_index] && depth_map_buffer[depth_index] > 0u) {
-                    break;
-                }
+                img[i] = u32(color[0] * 255.0) << 24 |
+                         u32(color[1] * 255.0) << 16 |
+                         u32(color[2] * 255.0) << 8  |
+                         u32(color[3] * 255.0);
+                result = DRAWN;
             }
         }
     }
+    return result;
+}
+
+// Color pass: only the winner of a screen position writes to it, so no locking is needed
+@compute @workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32
+) {
+    let index = id.x + id.y * groups.x * 64u;
+    var result = HIDDEN;
+    if (index < arrayLength(&pixels)) {
+        result = shade(index);
+    }
+
+    // Counted per workgroup first, so only one invocation of each adds to the totals
+    if ((uniforms.flags & FLAG_STATS) != 0u) {
+        if (result == DRAWN) {
+            atomicAdd(&visible_count, 1u);
+        } else if (result == CULLED) {
+            atomicAdd(&culled_count, 1u);
+        }
+        workgroupBarrier();
+        if (local_index == 0u) {
+            atomicAdd(&stats[0], atomicLoad(&visible_count));
+            atomicAdd(&stats[1], atomicLoad(&culled_count));
+        }
+    }
 }
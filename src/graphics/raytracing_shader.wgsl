@@ -34,13 +34,17 @@ struct Uniforms {
     ref_y: f32,
     ref_z: f32,
     z_offset: f32,
+    highlight_id: u32,
+    flags: u32,
+    debug_view: u32,
 };
 
+// Gizmos and other overlays are drawn on top of the scene, unlit
+const OVERLAY_ID: u32 = 0xfffffffeu;
+
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
-@group(0) @binding(1) var<storage, read_write> pixels: array<Pixel>;
-@group(0) @binding(2) var<storage, read_write> depth_buffer: array<f32>;
-@group(0) @binding(3) var<storage, read_write> depth_map_buffer: array<u32>;
-@group(0) @binding(4) var<storage, read_write> lock: array<atomic<u32>>;
+@group(0) @binding(1) var<storage, read> pixels: array<Pixel>;
+@group(0) @binding(2) var<storage, read_write> depth_buffer: array<atomic<u32>>;
 
 fn rotate(v: vec3<f32>, angle: vec3<f32>) -> vec3<f32> {
     let cos_x = cos(angle.x);
@@ -64,6 +68,15 @@ fn rotate(v: vec3<f32>, angle: vec3<f32>) -> vec3<f32> {
     return vec3<f32>(final_x, final_y, rotated_y.z);
 }
 
+// Maps a depth to a key whose unsigned order matches the float order, 0 is left for empty
+fn depth_key(z: f32) -> u32 {
+    let bits = bitcast<u32>(z);
+    if ((bits & 0x80000000u) != 0u) {
+        return ~bits;
+    }
+    return bits | 0x80000000u;
+}
+
 fn project(v: vec3<f32>, scale_factor: f32) -> vec2<i32> {
     return vec2<i32>(
         i32(v.x * scale_factor + uniforms.canvas_width / 2.0),
@@ -71,10 +84,16 @@ fn project(v: vec3<f32>, scale_factor: f32) -> vec2<i32> {
     );
 }
 
-@compute @workgroup_size(8, 8)
-fn main(@builtin(global_invocation_id) id: vec3<u32>) {
-    let index = id.x;
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = id.x + id.y * groups.x * 64u;
+    if (index >= arrayLength(&pixels)) {
+        return;
+    }
     let pixel = pixels[index];
+    if (pixel.id == OVERLAY_ID) {
+        return;
+    }
 
     var trasnformed_pixel = rotate(
         vec3<f32>(pixel.x, pixel.y, pixel.z),
@@ -106,19 +125,7 @@ fn main(@builtin(global_invocation_id) id: vec3<u32>) {
             }
 
             let depth_index = py_offset * canvas_width + px_offset;
-            while (true) {
-                if (atomicCompareExchangeWeak(&lock[depth_index], 0u, 1u).exchanged) {
-                    if (positioned_pixel.z > depth_buffer[depth_index] || depth_map_buffer[depth_index] == 0u) {
-                        depth_buffer[depth_index] = positioned_pixel.z;
-                        depth_map_buffer[depth_index] = pixel.id;
-                    }
-                    atomicStore(&lock[depth_index], 0u);
-                    break;
-                }
-                if (positioned_pixel.z <= depth_buffer[depth_index] && depth_map_buffer[depth_index] > 0u) {
-                    break;
-                }
-            }
+            atomicMax(&depth_buffer[depth_index], depth_key(positioned_pixel.z));
         }
     }
 }
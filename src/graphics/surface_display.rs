@@ -0,0 +1,188 @@
+use sdl2::video::Window;
+use crate::types::engine_error::EngineError;
+
+/// Presents the image buffer straight to the window's swapchain, so frames never leave the GPU.
+pub struct SurfaceDisplay {
+    // Declared before the window so it is dropped first
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    texture: wgpu::Texture,
+    to_texture_compute_pipeline: wgpu::ComputePipeline,
+    blit_render_pipeline: wgpu::RenderPipeline,
+    _window: Window,
+}
+
+impl SurfaceDisplay {
+    pub fn create_surface(instance: &wgpu::Instance, window: &Window) -> Result<wgpu::Surface<'static>, EngineError> {
+        // The window is moved into the SurfaceDisplay that owns the surface, so it outlives it
+        unsafe {
+            let target = wgpu::SurfaceTargetUnsafe::from_window(window).map_err(|error| EngineError::Window(error.to_string()))?;
+            instance
+                .create_surface_unsafe(target)
+                .map_err(|error| EngineError::Gpu(format!("could not create a surface for the window: {}", error)))
+        }
+    }
+
+    pub fn new(
+        surface: wgpu::Surface<'static>,
+        window: Window,
+        vsync: bool,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+    ) -> Result<SurfaceDisplay, EngineError> {
+        let (width, height) = window.size();
+        let capabilities = surface.get_capabilities(adapter);
+        if capabilities.formats.is_empty() {
+            return Err(EngineError::Gpu(String::from("the adapter cannot present to the window")));
+        }
+        // Colors are computed in display space already, like the ones SDL streams
+        let format = capabilities.formats.iter()
+            .copied()
+            .find(|format| !format.is_srgb())
+            .unwrap_or(capabilities.formats[0]);
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            // Without vsync the driver picks immediate or mailbox presentation, falling back to fifo
+            present_mode: if vsync { wgpu::PresentMode::Fifo } else { wgpu::PresentMode::AutoNoVsync },
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(device, &config);
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Present Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("present_shader.wgsl").into()),
+        });
+        let to_texture_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("To Texture Compute Pipeline"),
+            layout: None,
+            module: &shader_module,
+            entry_point: Some("to_texture"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let blit_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Render Pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Ok(SurfaceDisplay {
+            surface,
+            texture: create_frame_texture(device, width, height),
+            config,
+            to_texture_compute_pipeline,
+            blit_render_pipeline,
+            _window: window,
+        })
+    }
+
+    /// Finishes the frame recorded in `encoder` by copying `img_buffer` to the swapchain.
+    pub fn present(&self, device: &wgpu::Device, queue: &wgpu::Queue, mut encoder: wgpu::CommandEncoder, img_buffer: &wgpu::Buffer) {
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                self.surface.configure(device, &self.config);
+                queue.submit(Some(encoder.finish()));
+                return;
+            }
+            Err(_) => {
+                queue.submit(Some(encoder.finish()));
+                return;
+            }
+        };
+
+        let texture_view = self.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group_layout = self.to_texture_compute_pipeline.get_bind_group_layout(0);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: img_buffer.as_entire_binding(), },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&texture_view), },
+            ],
+            label: None,
+        });
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("To Texture Compute Pass"),
+                timestamp_writes: None,
+            });
+            cpass.set_pipeline(&self.to_texture_compute_pipeline);
+            cpass.set_bind_group(0, &bind_group, &[]);
+            cpass.dispatch_workgroups(self.config.width.div_ceil(8), self.config.height.div_ceil(8), 1);
+        }
+
+        let bind_group_layout = self.blit_render_pipeline.get_bind_group_layout(0);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&texture_view), },
+            ],
+            label: None,
+        });
+        let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Blit Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame_view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            rpass.set_pipeline(&self.blit_render_pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+
+        queue.submit(Some(encoder.finish()));
+        frame.present();
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.config.width = width;
+        self.config.height = height;
+        self.surface.configure(device, &self.config);
+        self.texture = create_frame_texture(device, width, height);
+    }
+}
+
+fn create_frame_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Frame Texture"),
+        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+use std::f32::consts::PI;
+use std::time::Instant;
+use crate::types::{engine_error::EngineError, light::Light, pixel::Pixel, renderer::Renderer, view_state::ViewState};
+use super::generator_helper::{Color, Generator, Transform};
+
+pub const DEFAULT_PIXEL_COUNTS: [usize; 4] = [10_000, 100_000, 1_000_000, 10_000_000];
+pub const DEFAULT_FRAMES: usize = 240;
+/// Frames rendered before timing starts, so buffer growth and shader compilation are not measured.
+const WARMUP_FRAMES: usize = 10;
+/// The frame size the benchmark view is framed for, other sizes scale it to fit.
+const REFERENCE_HEIGHT: f32 = 600.0;
+
+/// A sphere over a checkered plane with the given number of pixels in total, half of them in the plane.
+pub fn bench_scene(pixel_count: usize) -> Vec<Pixel> {
+    let plane = Generator {
+        transform: Transform::at(0.0, 0.0, -60.0),
+        color: Color::checker([0.9, 0.9, 0.9, 1.0], [0.6, 0.6, 0.6, 1.0], 8),
+        ..Generator::new(1, (pixel_count / 2) as f32 / (400.0 * 400.0))
+    };
+    let mut pixels = plane.plane(400.0, 400.0);
+    let remaining = pixel_count.saturating_sub(pixels.len()).max(1);
+    let sphere = Generator {
+        color: Color::Solid([0.9, 0.5, 0.2, 1.0]),
+        ..Generator::new(2, remaining as f32 / (4.0 * PI * 60.0 * 60.0))
+    };
+    pixels.extend(sphere.sphere(60.0));
+    pixels
+}
+
+/// The fixed camera path: one turn around the scene, tilting up and back down, with the light following.
+pub fn bench_view(frame: usize, frames: usize, height: u32) -> (ViewState, Light) {
+    let t = frame as f32 / frames.max(1) as f32;
+    let view_state = ViewState {
+        angle_x: 0.6 + 0.3 * (2.0 * PI * t).sin(),
+        angle_y: 0.0,
+        angle_z: 2.0 * PI * t,
+        c_angle_x: 0.0,
+        c_angle_y: 0.0,
+        c_angle_z: 0.0,
+        l_angle_x: 0.0,
+        l_angle_y: 0.0,
+        l_angle_z: 0.0,
+        camera_x: 0.0,
+        camera_y: 0.0,
+        camera_z: 500.0,
+        ref_x: 0.0,
+        ref_y: 0.0,
+        ref_z: 0.0,
+        z_offset: 50.0,
+        scale: 900.0 * height as f32 / REFERENCE_HEIGHT,
+    };
+    let light = Light { x: 200.0 * (2.0 * PI * t).cos(), y: 200.0 * (2.0 * PI * t).sin(), z: 400.0, intensity: 600.0 };
+    (view_state, light)
+}
+
+/// Summary of a set of timings in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub min: f32,
+    pub mean: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Stats {
+    pub fn new(samples: &[f32]) -> Stats {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        // Nearest rank, so a percentile is always one of the measured values
+        let percentile = |p: f32| sorted.get(((p * sorted.len() as f32).ceil() as usize).max(1) - 1).copied().unwrap_or(0.0);
+        Stats {
+            min: sorted.first().copied().unwrap_or(0.0),
+            mean: sorted.iter().sum::<f32>() / sorted.len().max(1) as f32,
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: sorted.last().copied().unwrap_or(0.0),
+        }
+    }
+
+    fn json(&self) -> String {
+        format!("{{ \"min\": {:.4}, \"mean\": {:.4}, \"p95\": {:.4}, \"p99\": {:.4}, \"max\": {:.4} }}", self.min, self.mean, self.p95, self.p99, self.max)
+    }
+}
+
+pub struct BenchResult {
+    pub pixel_count: usize,
+    pub frame: Stats,
+    /// Per pass timings, when the renderer can measure them.
+    pub passes: Vec<(&'static str, Stats)>,
+}
+
+/// Renders the camera path over whatever the renderer holds, timing each frame and its passes.
+pub fn run(renderer: &mut dyn Renderer, frames: usize, height: u32) -> Result<BenchResult, EngineError> {
+    renderer.set_profiling(true);
+    for frame in 0..WARMUP_FRAMES {
+        let (view_state, light) = bench_view(frame, WARMUP_FRAMES, height);
+        renderer.render(&view_state, &light)?;
+    }
+
+    let mut frame_times = Vec::with_capacity(frames);
+    let mut pass_times: Vec<(&'static str, Vec<f32>)> = Vec::new();
+    for frame in 0..frames {
+        let (view_state, light) = bench_view(frame, frames, height);
+        let start = Instant::now();
+        renderer.render(&view_state, &light)?;
+        frame_times.push(start.elapsed().as_secs_f32() * 1000.0);
+
+        for (name, time) in renderer.pass_timings() {
+            match pass_times.iter_mut().find(|(pass, _)| *pass == name) {
+                Some((_, times)) => times.push(time),
+                None => pass_times.push((name, vec![time])),
+            }
+        }
+    }
+    renderer.set_profiling(false);
+
+    Ok(BenchResult {
+        pixel_count: renderer.pixel_count(),
+        frame: Stats::new(&frame_times),
+        passes: pass_times.iter().map(|(name, times)| (*name, Stats::new(times))).collect(),
+    })
+}
+
+/// The report written for comparing runs, one entry per scene size.
+pub fn report_json(backend: &str, width: u32, height: u32, frames: usize, results: &[BenchResult]) -> String {
+    let scenes: Vec<String> = results
+        .iter()
+        .map(|result| {
+            let passes: Vec<String> = result.passes.iter().map(|(name, stats)| format!("        \"{}\": {}", name, stats.json())).collect();
+            format!(
+                "    {{\n      \"pixels\": {},\n      \"frame_ms\": {},\n      \"passes_ms\": {{{}}}\n    }}",
+                result.pixel_count,
+                result.frame.json(),
+                if passes.is_empty() { String::new() } else { format!("\n{}\n      ", passes.join(",\n")) },
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"version\": \"{}\",\n  \"backend\": \"{}\",\n  \"width\": {},\n  \"height\": {},\n  \"frames\": {},\n  \"scenes\": [\n{}\n  ]\n}}\n",
+        env!("CARGO_PKG_VERSION"),
+        backend.replace('\\', "\\\\").replace('"', "\\\""),
+        width,
+        height,
+        frames,
+        scenes.join(",\n"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_use_nearest_rank_percentiles() {
+        let samples: Vec<f32> = (1..=100).rev().map(|i| i as f32).collect();
+        let stats = Stats::new(&samples);
+        assert_eq!(stats, Stats { min: 1.0, mean: 50.5, p95: 95.0, p99: 99.0, max: 100.0 });
+        assert_eq!(Stats::new(&[4.0]).p99, 4.0);
+        assert_eq!(bench_scene(10_000).len(), 10_000);
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use crate::helpers::stream_helper::ChunkStreamer;
+use crate::scene::camera_path::CameraPath;
+use crate::types::{engine_error::EngineError, renderer::Renderer};
+use crate::types::view_state::ViewState;
+
+pub struct ExportSettings {
+    /// Directory for the numbered PNG frames, none to skip writing them.
+    pub dir: Option<PathBuf>,
+    /// Shell command that receives the frames as raw RGB bytes on its standard input.
+    pub encoder: Option<String>,
+    pub fps: f32,
+}
+
+/// Renders a camera path frame by frame at a fixed timestep, independent of how long each frame
+/// takes, returning the number of frames written. A streamed model has the chunks around each
+/// frame's camera loaded before the frame is rendered.
+pub async fn export_path(
+    renderer: &mut dyn Renderer<'_>,
+    mut streamer: Option<&mut ChunkStreamer>,
+    path: &CameraPath,
+    view_state: &ViewState,
+    settings: &ExportSettings,
+    width: u32,
+    height: u32,
+) -> Result<usize, EngineError> {
+    if let Some(dir) = &settings.dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut encoder = settings.encoder.as_deref().map(spawn_encoder).transpose()?;
+
+    let frame_count = (path.duration() * settings.fps).floor() as usize + 1;
+    let mut view_state = *view_state;
+    for frame in 0..frame_count {
+        let Some((camera, light)) = path.sample(frame as f32 / settings.fps) else {
+            break;
+        };
+        camera.apply(&mut view_state);
+        if let Some(streamer) = streamer.as_deref_mut() {
+            // Chunks load a few at a time, so the frame waits until nothing is left to load or evict
+            while streamer.update(&view_state, renderer).await? {}
+        }
+        renderer.render(&view_state, &light)?;
+        let rgb = to_rgb(&renderer.screenshot()?.0);
+
+        if let Some(dir) = &settings.dir {
+            write_png(&dir.join(format!("frame_{:06}.png", frame)), rgb.clone(), width, height)?;
+        }
+        if let Some(stdin) = encoder.as_mut().and_then(|encoder| encoder.stdin.as_mut()) {
+            stdin.write_all(&rgb)?;
+        }
+        print!("\rEXPORTED FRAME: {:6} / {}", frame + 1, frame_count);
+        io::stdout().flush()?;
+    }
+    println!();
+
+    if let Some(mut encoder) = encoder {
+        // Closing the input tells the encoder the video is complete
+        drop(encoder.stdin.take());
+        let status = encoder.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("encoder exited with {}", status)).into());
+        }
+    }
+    Ok(frame_count)
+}
+
+/// Converts the renderer's 0xRRGGBBAA words to RGB bytes, empty pixels becoming black.
+pub fn to_rgb(img: &[u8]) -> Vec<u8> {
+    img.chunks_exact(4).flat_map(|abgr| [abgr[3], abgr[2], abgr[1]]).collect()
+}
+
+pub fn write_png(path: &Path, rgb: Vec<u8>, width: u32, height: u32) -> io::Result<()> {
+    image::RgbImage::from_raw(width, height, rgb)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame does not match its size"))?
+        .save(path)
+        .map_err(io::Error::other)
+}
+
+fn spawn_encoder(command: &str) -> io::Result<Child> {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    Command::new(shell).args([flag, command]).stdin(Stdio::piped()).spawn()
+}
@@ -0,0 +1,312 @@
+#![allow(dead_code)]
+use std::f32::consts::PI;
+use crate::graphics::operations::Operations;
+use crate::types::pixel::Pixel;
+
+/// Scales, then rotates, then moves the generated pixels, the order the editor applies to its objects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub position: (f32, f32, f32),
+    pub angles: (f32, f32, f32),
+    pub scale: (f32, f32, f32),
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform { position: (0.0, 0.0, 0.0), angles: (0.0, 0.0, 0.0), scale: (1.0, 1.0, 1.0) }
+    }
+}
+
+impl Transform {
+    pub fn at(x: f32, y: f32, z: f32) -> Transform {
+        Transform { position: (x, y, z), ..Transform::default() }
+    }
+
+    fn apply(&self, (x, y, z): (f32, f32, f32)) -> (f32, f32, f32) {
+        let (x, y, z) = Operations::rotate((x * self.scale.0, y * self.scale.1, z * self.scale.2), self.angles);
+        (x + self.position.0, y + self.position.1, z + self.position.2)
+    }
+
+    /// The largest stretch along any axis, which pixel gaps grow by.
+    fn stretch(&self) -> f32 {
+        self.scale.0.abs().max(self.scale.1.abs()).max(self.scale.2.abs())
+    }
+}
+
+/// Where a pixel sits on its primitive before the transform. `u` and `v` run from 0 to 1,
+/// `u` around round primitives and along x on flat ones.
+#[derive(Clone, Copy, Debug)]
+pub struct SurfacePoint {
+    pub position: (f32, f32, f32),
+    pub u: f32,
+    pub v: f32,
+}
+
+pub type ColorFunction = Box<dyn Fn(&SurfacePoint) -> [f32; 4]>;
+
+pub enum Color {
+    Solid([f32; 4]),
+    Function(ColorFunction),
+}
+
+impl Color {
+    /// Alternates two colors over a grid of `cells` by `cells` squares in u and v.
+    pub fn checker(first: [f32; 4], second: [f32; 4], cells: usize) -> Color {
+        Color::Function(Box::new(move |point| {
+            let cell = |t: f32| ((t * cells as f32) as usize).min(cells.max(1) - 1);
+            if (cell(point.u) + cell(point.v)).is_multiple_of(2) { first } else { second }
+        }))
+    }
+
+    fn at(&self, point: &SurfacePoint) -> [f32; 4] {
+        match self {
+            Color::Solid(color) => *color,
+            Color::Function(color) => color(point),
+        }
+    }
+}
+
+/// Builds primitives out of pixels, centered on the origin with z as their axis before the transform.
+/// `density` is the pixels per unit of surface area; each pixel's `size_factor` is the gap to its neighbours,
+/// so the splats close up the surface.
+pub struct Generator {
+    pub id: u32,
+    pub density: f32,
+    pub transform: Transform,
+    pub color: Color,
+}
+
+impl Generator {
+    pub fn new(id: u32, density: f32) -> Generator {
+        Generator { id, density, transform: Transform::default(), color: Color::Solid([1.0, 1.0, 1.0, 1.0]) }
+    }
+
+    /// Distance between neighbouring pixels at the target density.
+    pub fn spacing(&self) -> f32 {
+        1.0 / self.density.sqrt()
+    }
+
+    /// Spreads the pixels evenly along a Fibonacci spiral from pole to pole.
+    pub fn sphere(&self, radius: f32) -> Vec<Pixel> {
+        let area = 4.0 * PI * radius * radius;
+        let count = ((area * self.density).ceil() as usize).max(1);
+        let size_factor = (area / count as f32).sqrt();
+        let golden_angle = PI * (3.0 - 5f32.sqrt());
+        let mut pixels = Vec::with_capacity(count);
+        for i in 0..count {
+            let z = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+            let ring = (1.0 - z * z).sqrt();
+            let theta = golden_angle * i as f32;
+            let u = (theta / (2.0 * PI)).fract();
+            self.push(&mut pixels, (radius * ring * theta.cos(), radius * ring * theta.sin(), radius * z), (u, 0.5 - 0.5 * z), size_factor);
+        }
+        pixels
+    }
+
+    /// A rectangle in the xy plane.
+    pub fn plane(&self, width: f32, depth: f32) -> Vec<Pixel> {
+        let mut pixels = Vec::new();
+        self.grid(&mut pixels, (width, depth), |x, y| (x, y, 0.0));
+        pixels
+    }
+
+    /// A box with only its faces filled.
+    pub fn cuboid(&self, (width, depth, height): (f32, f32, f32)) -> Vec<Pixel> {
+        let (hx, hy, hz) = (width / 2.0, depth / 2.0, height / 2.0);
+        let mut pixels = Vec::new();
+        for side in [-1.0, 1.0] {
+            self.grid(&mut pixels, (width, depth), |x, y| (x, y, side * hz));
+            self.grid(&mut pixels, (width, height), |x, z| (x, side * hy, z));
+            self.grid(&mut pixels, (depth, height), |y, z| (side * hx, y, z));
+        }
+        pixels
+    }
+
+    /// A closed cylinder around the z axis, `height` tall.
+    pub fn cylinder(&self, radius: f32, height: f32) -> Vec<Pixel> {
+        let rows = ((height / self.spacing()).ceil() as usize).max(1);
+        let row_spacing = height / rows as f32;
+        let mut pixels = Vec::new();
+        for row in 0..rows {
+            let v = (row as f32 + 0.5) / rows as f32;
+            self.ring(&mut pixels, radius, height * (v - 0.5), v, row_spacing);
+        }
+        self.disc(&mut pixels, radius, -height / 2.0);
+        self.disc(&mut pixels, radius, height / 2.0);
+        pixels
+    }
+
+    /// A closed cone pointing up the z axis, its base half of `height` below the origin.
+    pub fn cone(&self, radius: f32, height: f32) -> Vec<Pixel> {
+        let slant = (radius * radius + height * height).sqrt();
+        let rows = ((slant / self.spacing()).ceil() as usize).max(1);
+        let row_spacing = slant / rows as f32;
+        let mut pixels = Vec::new();
+        for row in 0..rows {
+            let v = (row as f32 + 0.5) / rows as f32;
+            self.ring(&mut pixels, radius * v, height / 2.0 - height * v, v, row_spacing);
+        }
+        self.disc(&mut pixels, radius, -height / 2.0);
+        pixels
+    }
+
+    /// A ring around the z axis, `major` from the axis to the middle of the tube and `minor` across the tube.
+    pub fn torus(&self, major: f32, minor: f32) -> Vec<Pixel> {
+        let rows = ((2.0 * PI * minor / self.spacing()).ceil() as usize).max(1);
+        let row_spacing = 2.0 * PI * minor / rows as f32;
+        let mut pixels = Vec::new();
+        for row in 0..rows {
+            let v = (row as f32 + 0.5) / rows as f32;
+            let phi = 2.0 * PI * v;
+            self.ring(&mut pixels, major + minor * phi.cos(), minor * phi.sin(), v, row_spacing);
+        }
+        pixels
+    }
+
+    /// Heights over a rectangle in the xy plane. The density counts the area of the rectangle, not of the slopes,
+    /// and each pixel's size reaches the farthest of its neighbours so steep slopes stay closed.
+    pub fn heightfield(&self, width: f32, depth: f32, height: impl Fn(f32, f32) -> f32) -> Vec<Pixel> {
+        let (columns, rows, step) = self.cells(width, depth);
+        let position = |i: usize, j: usize| (step.0 * (i as f32 + 0.5) - width / 2.0, step.1 * (j as f32 + 0.5) - depth / 2.0);
+        let heights: Vec<f32> = (0..rows).flat_map(|j| (0..columns).map(move |i| (i, j))).map(|(i, j)| {
+            let (x, y) = position(i, j);
+            height(x, y)
+        }).collect();
+
+        let mut pixels = Vec::with_capacity(heights.len());
+        for j in 0..rows {
+            for i in 0..columns {
+                let z = heights[j * columns + i];
+                let gap = |neighbour: Option<usize>, run: f32| neighbour.map_or(run, |index| (run * run + (heights[index] - z).powi(2)).sqrt());
+                let size_factor = gap(i.checked_sub(1).map(|i| j * columns + i), step.0)
+                    .max(gap((i + 1 < columns).then(|| j * columns + i + 1), step.0))
+                    .max(gap(j.checked_sub(1).map(|j| j * columns + i), step.1))
+                    .max(gap((j + 1 < rows).then(|| (j + 1) * columns + i), step.1));
+                let (x, y) = position(i, j);
+                let uv = ((i as f32 + 0.5) / columns as f32, (j as f32 + 0.5) / rows as f32);
+                self.push(&mut pixels, (x, y, z), uv, size_factor);
+            }
+        }
+        pixels
+    }
+
+    /// Columns and rows covering a rectangle at the target density, and the step between them.
+    fn cells(&self, width: f32, depth: f32) -> (usize, usize, (f32, f32)) {
+        let columns = ((width / self.spacing()).ceil() as usize).max(1);
+        let rows = ((depth / self.spacing()).ceil() as usize).max(1);
+        (columns, rows, (width / columns as f32, depth / rows as f32))
+    }
+
+    /// A flat rectangle centered on its own origin, placed into 3D by `place`.
+    fn grid(&self, pixels: &mut Vec<Pixel>, (width, depth): (f32, f32), place: impl Fn(f32, f32) -> (f32, f32, f32)) {
+        let (columns, rows, step) = self.cells(width, depth);
+        let size_factor = step.0.max(step.1);
+        for j in 0..rows {
+            for i in 0..columns {
+                let uv = ((i as f32 + 0.5) / columns as f32, (j as f32 + 0.5) / rows as f32);
+                self.push(pixels, place(width * (uv.0 - 0.5), depth * (uv.1 - 0.5)), uv, size_factor);
+            }
+        }
+    }
+
+    /// A circle around the z axis, `row_spacing` away from the circles next to it.
+    fn ring(&self, pixels: &mut Vec<Pixel>, radius: f32, z: f32, v: f32, row_spacing: f32) {
+        let circumference = 2.0 * PI * radius;
+        let count = ((circumference / self.spacing()).ceil() as usize).max(1);
+        let size_factor = (circumference / count as f32).max(row_spacing);
+        for i in 0..count {
+            let u = (i as f32 + 0.5) / count as f32;
+            let theta = 2.0 * PI * u;
+            self.push(pixels, (radius * theta.cos(), radius * theta.sin(), z), (u, v), size_factor);
+        }
+    }
+
+    /// A filled circle at height `z`, made of circles from the middle outwards.
+    fn disc(&self, pixels: &mut Vec<Pixel>, radius: f32, z: f32) {
+        let rows = ((radius / self.spacing()).ceil() as usize).max(1);
+        let row_spacing = radius / rows as f32;
+        for row in 0..rows {
+            let v = (row as f32 + 0.5) / rows as f32;
+            self.ring(pixels, radius * v, z, v, row_spacing);
+        }
+    }
+
+    fn push(&self, pixels: &mut Vec<Pixel>, position: (f32, f32, f32), (u, v): (f32, f32), size_factor: f32) {
+        let color = self.color.at(&SurfacePoint { position, u, v });
+        let (x, y, z) = self.transform.apply(position);
+        pixels.push(Pixel {
+            id: self.id,
+            x,
+            y,
+            z,
+            r: color[0],
+            g: color[1],
+            b: color[2],
+            a: color[3],
+            size_factor: size_factor * self.transform.stretch(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type OnSurface<'a> = Box<dyn Fn((f32, f32, f32)) -> bool + 'a>;
+
+    #[test]
+    fn primitives_lie_on_their_surfaces_at_the_target_density() {
+        let transform = Transform { position: (150.0, 50.0, -10.0), angles: (0.3, -0.7, 1.1), scale: (2.0, 2.0, 2.0) };
+        let generator = Generator { transform, ..Generator::new(3, 4.0) };
+        let local = |pixel: &Pixel| {
+            let (x, y, z) = Operations::rotate_inverse((pixel.x - 150.0, pixel.y - 50.0, pixel.z + 10.0), transform.angles);
+            (x / 2.0, y / 2.0, z / 2.0)
+        };
+        let near = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        let around = |x: f32, y: f32| (x * x + y * y).sqrt();
+
+        let slant = (5.0f32 * 5.0 + 12.0 * 12.0).sqrt();
+        let cases: Vec<(&str, Vec<Pixel>, f32, OnSurface)> = vec![
+            ("sphere", generator.sphere(10.0), 4.0 * PI * 100.0, Box::new(|(x, y, z)| near((x * x + y * y + z * z).sqrt(), 10.0))),
+            ("plane", generator.plane(30.0, 20.0), 600.0, Box::new(|(x, y, z)| near(z, 0.0) && x.abs() <= 15.0 && y.abs() <= 10.0)),
+            ("cuboid", generator.cuboid((10.0, 20.0, 30.0)), 2200.0, Box::new(|(x, y, z)| {
+                x.abs() <= 5.0 + 1e-3 && y.abs() <= 10.0 + 1e-3 && z.abs() <= 15.0 + 1e-3
+                    && (near(x.abs(), 5.0) || near(y.abs(), 10.0) || near(z.abs(), 15.0))
+            })),
+            ("cylinder", generator.cylinder(5.0, 20.0), 2.0 * PI * 5.0 * 20.0 + 2.0 * PI * 25.0, Box::new(move |(x, y, z)| {
+                (near(around(x, y), 5.0) && z.abs() <= 10.0) || (near(z.abs(), 10.0) && around(x, y) <= 5.0)
+            })),
+            ("cone", generator.cone(5.0, 12.0), PI * 5.0 * slant + PI * 25.0, Box::new(move |(x, y, z)| {
+                near(around(x, y), 5.0 * (6.0 - z) / 12.0) || (near(z, -6.0) && around(x, y) <= 5.0)
+            })),
+            ("torus", generator.torus(12.0, 3.0), 4.0 * PI * PI * 12.0 * 3.0, Box::new(move |(x, y, z)| {
+                near((around(x, y) - 12.0).powi(2) + z * z, 9.0)
+            })),
+        ];
+        for (name, pixels, area, on_surface) in cases {
+            let expected = area * generator.density;
+            let count = pixels.len() as f32;
+            assert!(count >= expected && count < 1.25 * expected, "{name} has {count} pixels for {expected}");
+            assert!(pixels.iter().all(|pixel| pixel.id == 3 && on_surface(local(pixel))), "{name} has pixels off its surface");
+            // Twice the size, so twice the gaps of at least the spacing
+            assert!(pixels.iter().all(|pixel| pixel.size_factor >= 2.0 * 0.95 * generator.spacing()), "{name} has pixels too small to close");
+        }
+    }
+
+    #[test]
+    fn steep_heightfields_grow_their_pixels_and_colors_follow_the_surface() {
+        let mut generator = Generator::new(1, 1.0);
+        generator.color = Color::Function(Box::new(|point| [point.u, point.v, point.position.2 / 30.0, 1.0]));
+        let pixels = generator.heightfield(10.0, 10.0, |x, _| 3.0 * x);
+        assert_eq!(pixels.len(), 100);
+        // Neighbours along x are one apart and three apart in height
+        assert!(pixels.iter().all(|pixel| (pixel.size_factor - 10f32.sqrt()).abs() < 1e-4));
+        assert!(pixels.iter().all(|pixel| pixel.b == pixel.z / 30.0 && pixel.r == (pixel.x + 5.0) / 10.0 && pixel.g == (pixel.y + 5.0) / 10.0));
+
+        generator.color = Color::checker([1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], 2);
+        let flat = generator.plane(4.0, 4.0);
+        let reds = flat.iter().filter(|pixel| pixel.r == 1.0).count();
+        assert_eq!((flat.len(), reds), (16, 8));
+        assert_eq!((flat[0].r, flat[3].r, flat[15].r), (1.0, 0.0, 1.0));
+    }
+}
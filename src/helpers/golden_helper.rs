@@ -0,0 +1,221 @@
+#![allow(dead_code)]
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::types::{engine_error::EngineError, light::Light, pixel::Pixel, renderer::Renderer, view_state::ViewState};
+use super::export_helper::{to_rgb, write_png};
+use super::generator_helper::{Color, Generator, Transform};
+use super::test_helper::{test_light, test_view_state};
+
+pub const GOLDEN_DIR: &str = "tests/golden";
+const DIFF_DIR: &str = "target/golden";
+/// Set to rewrite the reference images from the current output instead of comparing against them.
+pub const BLESS_VAR: &str = "BLESS_GOLDEN";
+
+/// The frame size the reference scenes are framed for.
+pub const REFERENCE_SIZE: (u32, u32) = (320, 240);
+/// Colors closer than this, in the YIQ distance scaled to 0..1, count as the same.
+pub const COLOR_THRESHOLD: f32 = 0.1;
+/// Share of pixels allowed to differ, for splat edges that land a pixel apart between drivers.
+pub const MISMATCH_RATIO: f32 = 0.005;
+
+/// A fixed scene whose rendering should only change on purpose.
+pub struct ReferenceScene {
+    pub name: &'static str,
+    pub objects: Vec<(u32, Vec<Pixel>)>,
+    pub view_state: ViewState,
+    pub light: Light,
+}
+
+pub fn reference_scenes() -> Vec<ReferenceScene> {
+    let cube = Generator {
+        color: Color::checker([1.0, 0.2, 0.2, 1.0], [0.2, 0.4, 1.0, 1.0], 4),
+        ..Generator::new(1, 6.0)
+    };
+    let sphere = Generator { color: Color::Solid([0.2, 0.6, 1.0, 1.0]), ..Generator::new(1, 8.0) };
+    let floor = Generator {
+        transform: Transform::at(0.0, 0.0, -40.0),
+        color: Color::checker([0.9, 0.9, 0.9, 1.0], [0.6, 0.6, 0.6, 1.0], 8),
+        ..Generator::new(1, 4.0)
+    };
+    let ball = Generator {
+        transform: Transform::at(0.0, 0.0, 10.0),
+        color: Color::Solid([1.0, 0.3, 0.2, 1.0]),
+        ..Generator::new(2, 6.0)
+    };
+    let mut sphere_view = test_view_state();
+    sphere_view.camera_z = 150.0;
+    let mut shadow_view = test_view_state();
+    (shadow_view.angle_x, shadow_view.angle_y, shadow_view.camera_z) = (0.3, 0.0, 200.0);
+
+    vec![
+        ReferenceScene {
+            name: "cube",
+            objects: vec![(1, cube.cuboid((23.0, 23.0, 23.0)))],
+            view_state: test_view_state(),
+            light: test_light(),
+        },
+        ReferenceScene {
+            name: "sphere",
+            objects: vec![(1, sphere.sphere(40.0))],
+            view_state: sphere_view,
+            light: Light { x: 60.0, y: 60.0, z: 150.0, intensity: 150.0 },
+        },
+        ReferenceScene {
+            name: "shadow",
+            objects: vec![
+                (1, floor.plane(200.0, 200.0)),
+                (2, ball.sphere(25.0)),
+            ],
+            view_state: shadow_view,
+            light: Light { x: 80.0, y: -80.0, z: 180.0, intensity: 200.0 },
+        },
+    ]
+}
+
+impl ReferenceScene {
+    pub fn load(&self, renderer: &mut dyn Renderer) {
+        for (id, pixels) in &self.objects {
+            renderer.add_object(*id, pixels.clone());
+        }
+    }
+}
+
+/// Renders the scene once and returns the frame as RGB bytes.
+pub fn render_scene(renderer: &mut dyn Renderer, scene: &ReferenceScene) -> Result<(Vec<u8>, u32, u32), EngineError> {
+    scene.load(renderer);
+    render_rgb(renderer, &scene.view_state, &scene.light)
+}
+
+pub fn render_rgb(renderer: &mut dyn Renderer, view_state: &ViewState, light: &Light) -> Result<(Vec<u8>, u32, u32), EngineError> {
+    renderer.render(view_state, light)?;
+    let (frame, width, height) = renderer.screenshot()?;
+    Ok((to_rgb(&frame), width, height))
+}
+
+/// How two images of the same size differ, with a picture of where.
+pub struct ImageDiff {
+    pub mismatched: usize,
+    pub total: usize,
+    pub max_delta: f32,
+    pub mean_delta: f32,
+    /// The expected image dimmed to gray with the mismatched pixels in red.
+    pub image: Vec<u8>,
+}
+
+impl ImageDiff {
+    pub fn ratio(&self) -> f32 {
+        self.mismatched as f32 / self.total.max(1) as f32
+    }
+}
+
+/// Compares two RGB images by perceived color, weighing brightness above hue.
+pub fn compare(expected: &[u8], actual: &[u8], threshold: f32) -> ImageDiff {
+    let mut diff = ImageDiff { mismatched: 0, total: expected.len() / 3, max_delta: 0.0, mean_delta: 0.0, image: Vec::with_capacity(expected.len()) };
+    for (a, b) in expected.chunks_exact(3).zip(actual.chunks_exact(3)) {
+        let delta = color_delta(a, b);
+        diff.max_delta = diff.max_delta.max(delta);
+        diff.mean_delta += delta;
+        if delta > threshold {
+            diff.mismatched += 1;
+            diff.image.extend_from_slice(&[255, 0, 0]);
+        } else {
+            let gray = (luma(a) * 0.3 * 255.0 + 64.0) as u8;
+            diff.image.extend_from_slice(&[gray, gray, gray]);
+        }
+    }
+    diff.mean_delta /= diff.total.max(1) as f32;
+    diff
+}
+
+/// Compares the frame with the checked-in image of that name, or writes the image instead when blessing
+/// is asked for. A missing image fails rather than blessing itself. Failed comparisons leave the actual
+/// and diff images in target/golden.
+pub fn check(name: &str, rgb: &[u8], width: u32, height: u32) -> Result<(), String> {
+    let reference = Path::new(GOLDEN_DIR).join(format!("{}.png", name));
+    if env::var_os(BLESS_VAR).is_some() {
+        std::fs::create_dir_all(GOLDEN_DIR).map_err(|error| error.to_string())?;
+        write_png(&reference, rgb.to_vec(), width, height).map_err(|error| error.to_string())?;
+        eprintln!("wrote golden image {}", reference.display());
+        return Ok(());
+    }
+    if !reference.is_file() {
+        return Err(format!("{}: no golden image, run the tests with {}=1 to write it", reference.display(), BLESS_VAR));
+    }
+
+    let expected = image::open(&reference).map_err(|error| format!("{}: {}", reference.display(), error))?.to_rgb8();
+    if expected.dimensions() != (width, height) {
+        return Err(format!("{}: is {}x{}, the frame is {}x{}", reference.display(), expected.width(), expected.height(), width, height));
+    }
+    let diff = compare(expected.as_raw(), rgb, COLOR_THRESHOLD);
+    if diff.ratio() <= MISMATCH_RATIO {
+        return Ok(());
+    }
+
+    let (actual_path, diff_path) = write_failure(name, rgb, &diff.image, width, height).map_err(|error| error.to_string())?;
+    Err(format!(
+        "{}: {} of {} pixels differ ({:.2}%, max delta {:.3}), see {} and {}",
+        name, diff.mismatched, diff.total, diff.ratio() * 100.0, diff.max_delta, actual_path.display(), diff_path.display()
+    ))
+}
+
+fn write_failure(name: &str, rgb: &[u8], diff: &[u8], width: u32, height: u32) -> io::Result<(PathBuf, PathBuf)> {
+    std::fs::create_dir_all(DIFF_DIR)?;
+    let actual_path = Path::new(DIFF_DIR).join(format!("{}.actual.png", name));
+    let diff_path = Path::new(DIFF_DIR).join(format!("{}.diff.png", name));
+    write_png(&actual_path, rgb.to_vec(), width, height)?;
+    write_png(&diff_path, diff.to_vec(), width, height)?;
+    Ok((actual_path, diff_path))
+}
+
+fn luma(rgb: &[u8]) -> f32 {
+    (0.299 * rgb[0] as f32 + 0.587 * rgb[1] as f32 + 0.114 * rgb[2] as f32) / 255.0
+}
+
+// The YIQ distance of pixelmatch, normalized so black against white is 1
+fn color_delta(a: &[u8], b: &[u8]) -> f32 {
+    let yiq = |rgb: &[u8]| {
+        let (r, g, b) = (rgb[0] as f32, rgb[1] as f32, rgb[2] as f32);
+        (
+            0.298_895_3 * r + 0.586_622_5 * g + 0.114_482_2 * b,
+            0.595_977_9 * r - 0.274_176_1 * g - 0.321_801_8 * b,
+            0.211_470_2 * r - 0.522_617_4 * g + 0.311_147_2 * b,
+        )
+    };
+    let (a, b) = (yiq(a), yiq(b));
+    let (y, i, q) = (a.0 - b.0, a.1 - b.1, a.2 - b.2);
+    ((0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / 35215.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use crate::graphics::{cpu_renderer::CpuRenderer, gpu_renderer::{AdapterOptions, GpuRenderer}};
+
+    fn check_all(backend: &str, mut renderer: impl FnMut() -> Box<dyn Renderer<'static>>) {
+        let failures: Vec<String> = reference_scenes()
+            .iter()
+            .filter_map(|scene| {
+                let (rgb, width, height) = render_scene(renderer().as_mut(), scene).unwrap();
+                check(&format!("{}_{}", scene.name, backend), &rgb, width, height).err()
+            })
+            .collect();
+        assert!(failures.is_empty(), "golden images differ:\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn cpu_frames_match_golden_images() {
+        check_all("cpu", || Box::new(CpuRenderer::headless(REFERENCE_SIZE.0, REFERENCE_SIZE.1)));
+    }
+
+    #[test]
+    fn gpu_frames_match_golden_images() {
+        let options = AdapterOptions { backends: wgpu::Backends::all(), allow_software: true };
+        if task::block_on(GpuRenderer::headless(1, 1, &options)).is_err() {
+            eprintln!("no gpu adapter available, skipping");
+            return;
+        }
+        check_all("gpu", || Box::new(task::block_on(GpuRenderer::headless(REFERENCE_SIZE.0, REFERENCE_SIZE.1, &options)).unwrap()));
+    }
+}
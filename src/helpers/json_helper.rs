@@ -0,0 +1,207 @@
+use std::io;
+
+/// A parsed JSON value. Numbers keep their text so each reader picks the precision it needs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> io::Result<Json> {
+        let mut parser = Parser { text: text.as_bytes(), at: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.at != text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("invalid JSON at byte {}: {}", self.at, message))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.text.get(self.at).is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.at).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> io::Result<()> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.at += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> io::Result<Json> {
+        if !self.text[self.at..].starts_with(word.as_bytes()) {
+            return Err(self.error("unknown literal"));
+        }
+        self.at += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> io::Result<Json> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn object(&mut self) -> io::Result<Json> {
+        self.expect(b'{')?;
+        let mut fields = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.at += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            fields.push((key, self.value()?));
+            match self.peek() {
+                Some(b',') => self.at += 1,
+                Some(b'}') => {
+                    self.at += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> io::Result<Json> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        if self.peek() == Some(b']') {
+            self.at += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.at += 1,
+                Some(b']') => {
+                    self.at += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(&byte) = self.text.get(self.at) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.at += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = self.text.get(self.at).copied().ok_or_else(|| self.error("unterminated string"))?;
+                    self.at += 1;
+                    let character = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let hex = self.text.get(self.at..self.at + 4).and_then(|hex| std::str::from_utf8(hex).ok());
+                            let code = hex.and_then(|hex| u32::from_str_radix(hex, 16).ok()).ok_or_else(|| self.error("invalid unicode escape"))?;
+                            self.at += 4;
+                            // Surrogate pairs are not needed by the files read here
+                            char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    bytes.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                byte if byte < 0x20 => return Err(self.error("control character in string")),
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    fn number(&mut self) -> io::Result<Json> {
+        let start = self.at;
+        while self.text.get(self.at).is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.at += 1;
+        }
+        let number = std::str::from_utf8(&self.text[start..self.at]).unwrap_or_default();
+        // Rust's float grammar is looser than JSON's, so the JSON shape is checked here
+        let digits = number.strip_prefix('-').unwrap_or(number);
+        let (mantissa, exponent) = digits.split_once(['e', 'E']).unwrap_or((digits, "0"));
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, "0"));
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let all_digits = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+        if !all_digits(whole) || (whole.len() > 1 && whole.starts_with('0')) || !all_digits(fraction) || !all_digits(exponent) {
+            return Err(self.error("invalid number"));
+        }
+        Ok(Json::Number(number.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values_and_rejects_what_json_does_not_allow() {
+        let json = Json::parse(r#" { "a": [1, -2.5e-3, true, null], "b": { "c": "x\"é\n" }, "d": [] } "#).unwrap();
+        assert_eq!(json.get("a"), Some(&Json::Array(vec![
+            Json::Number("1".into()), Json::Number("-2.5e-3".into()), Json::Bool(true), Json::Null,
+        ])));
+        assert_eq!(json.get("b").and_then(|b| b.get("c")), Some(&Json::String("x\"é\n".into())));
+        assert_eq!(json.get("d"), Some(&Json::Array(Vec::new())));
+        assert_eq!(json.get("e"), None);
+
+        for invalid in ["", "{", "{\"a\" 1}", "{\"a\": 1,}", "[1 2]", "NaN", "-inf", "01", "1.", ".5", "1e", "\"a", "nul", "{} {}"] {
+            let error = Json::parse(invalid).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{:?}", invalid);
+        }
+    }
+}
@@ -1,2 +1,10 @@
 pub mod test_helper;
 pub mod model_helper;
+pub mod stream_helper;
+pub mod export_helper;
+pub mod screenshot_helper;
+pub mod golden_helper;
+pub mod parity_helper;
+pub mod bench_helper;
+pub mod generator_helper;
+pub mod json_helper;
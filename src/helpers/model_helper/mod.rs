@@ -1,68 +1,32 @@
 #![allow(dead_code)]
 use std::env;
+use std::path::Path;
 use async_std::fs;
-use image::GenericImageView;
-use crate::types::{pixel::Pixel, view_state::ViewState};
+use crate::types::{engine_error::EngineError, pixel::Pixel, view_state::ViewState};
 use crate::graphics::operations::Operations;
 
-pub async fn load_msh_file_with_texture(id: u32) -> (Vec<Pixel>, usize) {
-    let mut pixels: Vec<Pixel> = vec![];
-    let current_dir = env::current_dir().expect("Failed to get current directory");
-    
-    let mesh_file_path = current_dir.join("mesh_output.msh");
-    let msh_bytes = fs::read(mesh_file_path).await.unwrap();
-    let parser_result = mshio::parse_msh_bytes(msh_bytes.as_slice());
+const WIDTH: f32 = 800.0;
+const HEIGHT: f32 = 800.0;
 
-    let texture_file_path = current_dir.join("texture.png");
-    let img = fs::read(texture_file_path).await.expect("Failed to read image");
-    let img = image::load_from_memory(&img).expect("Failed to decode image").to_rgba8();
-
-    let width = 800.0;
-    let height = 800.0;
+pub async fn load_msh_file_with_texture(id: u32) -> Result<(Vec<Pixel>, usize), EngineError> {
+    let mut pixels: Vec<Pixel> = vec![];
+    let count = read_msh_file_with_texture(id, |pixel| {
+        pixels.push(pixel);
+        Ok(())
+    }).await?;
+    Ok((pixels, count))
+}
 
-    let mut count = 0;
+/// Hands each textured pixel of the model to `visit` as it is made, so the pixels never have to fit in memory
+/// together. Returns the number of pixels.
+pub async fn read_msh_file_with_texture(id: u32, mut visit: impl FnMut(Pixel) -> Result<(), EngineError>) -> Result<usize, EngineError> {
+    let current_dir = env::current_dir().map_err(|error| EngineError::Init(format!("no current directory: {}", error)))?;
 
-    if let Some(nodes) = parser_result.unwrap().data.nodes {
-        for node_block in nodes.node_blocks {
-            for node in node_block.nodes {
-                let x = node.x as f32;
-                let y = node.y as f32;
-                let z = node.z as f32;
-
-                let angle_x: f32 = 4.5;
-                let angle_y: f32 = 0.0;
-                let (rx, ry, rz) = Operations::rotate(
-                    (x, y, z), 
-                    (angle_x, angle_y, 0.0)
-                );
-                let scale_factor = 280.0 / (220.0 + rz * 0.0);
-                let (tx, ty) = Operations::project(
-                    (rx, ry, rz), 
-                    scale_factor,
-                    width as f32, 
-                    height as f32
-                );
-                let rgba = img.get_pixel(tx as u32, ty as u32);
-
-                pixels.push(Pixel {
-                    id,
-                    x: rx,
-                    y: ry,
-                    z: rz,
-                    r: rgba[0] as f32 / 255.0,
-                    g: rgba[1] as f32 / 255.0,
-                    b: rgba[2] as f32 / 255.0,
-                    a: rgba[3] as f32 / 255.0,
-                    size_factor: 1.0,
-                });
-
-                count += 1;
-            }
-        }
-    }
+    let texture_path = current_dir.join("texture.png");
+    let img = load_image(&texture_path).await?;
 
     // nature PNG Designed By maafin from https://pngtree.com/freepng/red-flowers-3d_4825408.html?sol=downref&id=bef
-    let view_state = ViewState { 
+    let view_state = ViewState {
         angle_x: 0.0,
         angle_y: 0.0,
         angle_z: 0.0,
@@ -81,9 +45,9 @@ pub async fn load_msh_file_with_texture(id: u32) -> (Vec<Pixel>, usize) {
         ref_z: 0.0,
         z_offset: 30.0,
     };
-    load_texture(&mut pixels, count, view_state, width, height, "flower.png", 0, 40).await;
+    let front = Decal::load(&current_dir.join("flower.png"), view_state, 0, 40).await?;
 
-    let view_state = ViewState { 
+    let view_state = ViewState {
         angle_x: 0.45,
         angle_y: 3.32,
         angle_z: 0.0,
@@ -102,44 +66,119 @@ pub async fn load_msh_file_with_texture(id: u32) -> (Vec<Pixel>, usize) {
         ref_z: 0.0,
         z_offset: 30.0,
     };
-    load_texture(&mut pixels, count, view_state, width, height, "flower.png",0, 40).await;
+    let back = Decal::load(&current_dir.join("flower.png"), view_state, 0, 40).await?;
+
+    let mesh_file_path = current_dir.join("mesh_output.msh");
+    let msh_bytes = fs::read(&mesh_file_path).await.map_err(EngineError::asset(&mesh_file_path))?;
+    let parser_result = mshio::parse_msh_bytes(msh_bytes.as_slice()).map_err(EngineError::asset(&mesh_file_path))?;
+    let nodes = parser_result.data.nodes.into_iter().flat_map(|nodes| nodes.node_blocks).flat_map(|block| block.nodes);
+
+    let mut count = 0;
+    for node in nodes {
+        let (x, y, z) = (node.x, node.y, node.z);
+
+        let angle_x: f32 = 4.5;
+        let angle_y: f32 = 0.0;
+        let (rx, ry, rz) = Operations::rotate(
+            (x as f32, y as f32, z as f32),
+            (angle_x, angle_y, 0.0)
+        );
+        let scale_factor = 280.0 / (220.0 + rz * 0.0);
+        let (tx, ty) = Operations::project(
+            (rx, ry, rz),
+            scale_factor,
+            WIDTH,
+            HEIGHT
+        );
+        let rgba = texel(&img, tx, ty).ok_or_else(|| EngineError::Asset {
+            path: texture_path.clone(),
+            message: format!("node ({}, {}, {}) lands outside the texture at ({}, {})", x, y, z, tx, ty),
+        })?;
+
+        let mut pixel = Pixel {
+            id,
+            x: rx,
+            y: ry,
+            z: rz,
+            r: rgba[0] as f32 / 255.0,
+            g: rgba[1] as f32 / 255.0,
+            b: rgba[2] as f32 / 255.0,
+            a: rgba[3] as f32 / 255.0,
+            size_factor: 1.0,
+        };
+        front.paint(&mut pixel);
+        back.paint(&mut pixel);
+        visit(pixel)?;
+
+        count += 1;
+    }
+    Ok(count)
+}
+
+async fn load_image(path: &Path) -> Result<image::RgbaImage, EngineError> {
+    let img = fs::read(path).await.map_err(EngineError::asset(path))?;
+    Ok(image::load_from_memory(&img).map_err(EngineError::asset(path))?.to_rgba8())
+}
 
-    (pixels, count)
+/// The image's color at projected coordinates, or None when they fall outside it.
+fn texel(img: &image::RgbaImage, x: i32, y: i32) -> Option<image::Rgba<u8>> {
+    img.get_pixel_checked(u32::try_from(x).ok()?, u32::try_from(y).ok()?).copied()
 }
 
-async fn load_texture(pixels: &mut Vec<Pixel>, count: usize, view_state: ViewState, width: f32, height: f32, path: &str, wd: u32, hd: u32) {
-    let current_dir = env::current_dir().expect("Failed to get current directory");
-    let texture_file_path = current_dir.join(path);
-    let img = fs::read(texture_file_path).await.expect("Failed to read image");
-    let img = image::load_from_memory(&img).expect("Failed to decode image").to_rgba8();
-    
-    let (f_width, f_height) = img.dimensions();
-    let w_disposition = (width as u32 / 2) - (f_width / 2) + wd;
-    let h_disposition = (height as u32 / 2) - (f_height / 2) + hd;
+/// An image projected onto the pixels facing `view_state`, shifted by `wd`, `hd` screen pixels.
+struct Decal {
+    img: image::RgbaImage,
+    view_state: ViewState,
+    w_disposition: i32,
+    h_disposition: i32,
+}
 
-    for i in 0..count {
-        let pixel = &mut pixels[i];
+impl Decal {
+    async fn load(path: &Path, view_state: ViewState, wd: i32, hd: i32) -> Result<Decal, EngineError> {
+        let img = load_image(path).await?;
+        let (f_width, f_height) = img.dimensions();
+        // Images larger than the projection make the disposition negative, which only moves them
+        let w_disposition = (WIDTH as i32 / 2) - (f_width as i32 / 2) + wd;
+        let h_disposition = (HEIGHT as i32 / 2) - (f_height as i32 / 2) + hd;
+        Ok(Decal { img, view_state, w_disposition, h_disposition })
+    }
 
+    fn paint(&self, pixel: &mut Pixel) {
+        let view_state = &self.view_state;
         let (rx, ry, rz) = Operations::rotate(
-            (pixel.x, pixel.y, pixel.z), 
-            (view_state.angle_x, view_state.angle_y, 0.0) 
+            (pixel.x, pixel.y, pixel.z),
+            (view_state.angle_x, view_state.angle_y, 0.0)
         );
         let scale_factor = view_state.scale / (view_state.camera_z - rz);
         let (tx, ty) = Operations::project(
-            (rx, ry, rz), 
+            (rx, ry, rz),
             scale_factor,
-            width as f32, 
-            height as f32
+            WIDTH,
+            HEIGHT
         );
 
-        if rz < 0.0 && img.in_bounds(tx as u32 - w_disposition, ty as u32 - h_disposition) {
-            let rgba = img.get_pixel(tx as u32 - w_disposition, ty as u32 - h_disposition);
-            if rgba[3] != 0 {
-                pixel.r = rgba[0] as f32 / 255.0;
-                pixel.g = rgba[1] as f32 / 255.0;
-                pixel.b = rgba[2] as f32 / 255.0;
-                pixel.a = rgba[3] as f32 / 255.0;
-            }
+        if rz >= 0.0 {
+            return;
+        }
+        if let Some(rgba) = texel(&self.img, tx - self.w_disposition, ty - self.h_disposition).filter(|rgba| rgba[3] != 0) {
+            pixel.r = rgba[0] as f32 / 255.0;
+            pixel.g = rgba[1] as f32 / 255.0;
+            pixel.b = rgba[2] as f32 / 255.0;
+            pixel.a = rgba[3] as f32 / 255.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texels_outside_the_image_are_none() {
+        let img = image::RgbaImage::from_fn(4, 3, |x, y| image::Rgba([x as u8, y as u8, 0, 255]));
+        assert_eq!(texel(&img, 3, 2), Some(image::Rgba([3, 2, 0, 255])));
+        for (x, y) in [(-1, 0), (0, -1), (4, 0), (0, 3), (i32::MIN, i32::MAX)] {
+            assert_eq!(texel(&img, x, y), None, "{} {}", x, y);
         }
     }
 }
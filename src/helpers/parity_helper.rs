@@ -0,0 +1,116 @@
+#![allow(dead_code)]
+use std::io;
+use std::path::Path;
+use crate::types::{engine_error::EngineError, light::Light, renderer::Renderer, view_state::ViewState};
+use super::export_helper::write_png;
+use super::golden_helper::{compare, render_rgb, ImageDiff, COLOR_THRESHOLD};
+
+/// How the cpu and gpu renderers drew the same view.
+pub struct ParityReport {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub cpu: Vec<u8>,
+    pub gpu: Vec<u8>,
+    pub diff: ImageDiff,
+    /// Pixels only one of the renderers drew anything on.
+    pub cpu_only: usize,
+    pub gpu_only: usize,
+    /// Mean absolute difference of the red, green and blue channels, 0 to 255, where both drew.
+    pub channel_means: [f32; 3],
+}
+
+impl ParityReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{:<10} {:6.2}% of pixels differ (mean delta {:.3}, max {:.3})\t\tONLY CPU: {:6}\t\tONLY GPU: {:6}\t\tCHANNELS: r {:.1} g {:.1} b {:.1}",
+            self.name, self.diff.ratio() * 100.0, self.diff.mean_delta, self.diff.max_delta, self.cpu_only, self.gpu_only,
+            self.channel_means[0], self.channel_means[1], self.channel_means[2],
+        )
+    }
+
+    /// Writes NAME_cpu.png, NAME_gpu.png and NAME_diff.png with the differing pixels in red.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        write_png(&dir.join(format!("{}_cpu.png", self.name)), self.cpu.clone(), self.width, self.height)?;
+        write_png(&dir.join(format!("{}_gpu.png", self.name)), self.gpu.clone(), self.width, self.height)?;
+        write_png(&dir.join(format!("{}_diff.png", self.name)), self.diff.image.clone(), self.width, self.height)
+    }
+}
+
+/// Renders the view through both renderers, which have to hold the same pixels, and compares the frames.
+pub fn compare_backends(name: &str, cpu: &mut dyn Renderer, gpu: &mut dyn Renderer, view_state: &ViewState, light: &Light) -> Result<ParityReport, EngineError> {
+    let (cpu_frame, width, height) = render_rgb(cpu, view_state, light)?;
+    let (gpu_frame, gpu_width, gpu_height) = render_rgb(gpu, view_state, light)?;
+    if (width, height) != (gpu_width, gpu_height) {
+        return Err(EngineError::Gpu(format!("the gpu frame is {}x{}, the cpu one {}x{}", gpu_width, gpu_height, width, height)));
+    }
+
+    let (mut cpu_only, mut gpu_only, mut both) = (0, 0, 0);
+    let mut channel_sums = [0.0; 3];
+    for (a, b) in cpu_frame.chunks_exact(3).zip(gpu_frame.chunks_exact(3)) {
+        // Nothing is drawn where the frame stays black
+        match (a != [0, 0, 0], b != [0, 0, 0]) {
+            (true, false) => cpu_only += 1,
+            (false, true) => gpu_only += 1,
+            (true, true) => {
+                both += 1;
+                for channel in 0..3 {
+                    channel_sums[channel] += (a[channel] as f32 - b[channel] as f32).abs();
+                }
+            },
+            (false, false) => {}
+        }
+    }
+
+    Ok(ParityReport {
+        name: name.to_string(),
+        width,
+        height,
+        diff: compare(&cpu_frame, &gpu_frame, COLOR_THRESHOLD),
+        cpu: cpu_frame,
+        gpu: gpu_frame,
+        cpu_only,
+        gpu_only,
+        channel_means: channel_sums.map(|sum| sum / both.max(1) as f32),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use crate::graphics::{cpu_renderer::CpuRenderer, gpu_renderer::{AdapterOptions, GpuRenderer}};
+    use crate::helpers::golden_helper::{reference_scenes, REFERENCE_SIZE};
+
+    #[test]
+    fn identical_renderers_have_no_differences() {
+        let scene = &reference_scenes()[0];
+        let (mut first, mut second) = (CpuRenderer::headless(REFERENCE_SIZE.0, REFERENCE_SIZE.1), CpuRenderer::headless(REFERENCE_SIZE.0, REFERENCE_SIZE.1));
+        scene.load(&mut first);
+        scene.load(&mut second);
+        let report = compare_backends(scene.name, &mut first, &mut second, &scene.view_state, &scene.light).unwrap();
+        assert_eq!((report.diff.mismatched, report.cpu_only, report.gpu_only), (0, 0, 0));
+        assert_eq!(report.channel_means, [0.0; 3]);
+    }
+
+    #[test]
+    fn reports_where_the_backends_differ() {
+        // Any adapter will do, software ones included, since the point is comparing the two code paths
+        let options = AdapterOptions { backends: wgpu::Backends::all(), allow_software: true };
+        for scene in reference_scenes() {
+            let Ok(mut gpu) = task::block_on(GpuRenderer::headless(REFERENCE_SIZE.0, REFERENCE_SIZE.1, &options)) else {
+                eprintln!("no gpu adapter available, skipping");
+                return;
+            };
+            let mut cpu = CpuRenderer::headless(REFERENCE_SIZE.0, REFERENCE_SIZE.1);
+            scene.load(&mut cpu);
+            scene.load(&mut gpu);
+
+            let report = compare_backends(scene.name, &mut cpu, &mut gpu, &scene.view_state, &scene.light).unwrap();
+            eprintln!("{}", report.summary());
+            assert_eq!(report.diff.total, (REFERENCE_SIZE.0 * REFERENCE_SIZE.1) as usize);
+            assert!(report.cpu.iter().any(|&value| value > 0) && report.gpu.iter().any(|&value| value > 0), "{} was not drawn", scene.name);
+        }
+    }
+}
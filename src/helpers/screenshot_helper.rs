@@ -0,0 +1,151 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::types::{engine_error::EngineError, light::Light, renderer::Renderer, view_state::ViewState};
+use super::export_helper::{to_rgb, write_png};
+use super::json_helper::Json;
+
+/// Saves the last rendered frame as the next free screenshot_NNNN.png in the directory, with a
+/// .json sidecar holding everything needed to render the same view again.
+pub fn save_screenshot(dir: &Path, renderer: &mut dyn Renderer, view_state: &ViewState, light: &Light) -> Result<PathBuf, EngineError> {
+    std::fs::create_dir_all(dir)?;
+    let (frame, width, height) = renderer.screenshot()?;
+    let mut number = 1;
+    while dir.join(format!("screenshot_{:04}.png", number)).exists() {
+        number += 1;
+    }
+    let path = dir.join(format!("screenshot_{:04}.png", number));
+    write_png(&path, to_rgb(&frame), width, height)?;
+
+    let sidecar = format!(
+        "{{\n  \"image\": \"{}\",\n  \"width\": {},\n  \"height\": {},\n  \"backend\": \"{}\",\n  \"pixel_count\": {},\n  \"view_state\": {{\n{}\n  }},\n  \"light\": {{\n{}\n  }}\n}}\n",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        width,
+        height,
+        escape(&renderer.backend()),
+        renderer.pixel_count(),
+        fields(&view_state_fields(view_state)),
+        fields(&[("x", light.x), ("y", light.y), ("z", light.z), ("intensity", light.intensity)]),
+    );
+    std::fs::write(path.with_extension("json"), sidecar)?;
+    Ok(path)
+}
+
+/// Reads the view state and light back from a screenshot sidecar. Values that were not finite when
+/// the screenshot was taken are saved as null and can't be loaded.
+pub fn load_view(path: &Path) -> io::Result<(ViewState, Light)> {
+    let json = Json::parse(&std::fs::read_to_string(path)?)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path.display(), error)))?;
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+    let section = |name: &str| json.get(name).ok_or_else(|| invalid(format!("missing \"{}\"", name)));
+    let (view_section, light_section) = (section("view_state")?, section("light")?);
+    let number = |section: &Json, name: &str| match section.get(name) {
+        Some(Json::Number(number)) => number.parse::<f32>().map_err(|error| invalid(format!("\"{}\": {}", name, error))),
+        Some(Json::Null) => Err(invalid(format!("\"{}\" was not a finite number", name))),
+        Some(_) => Err(invalid(format!("\"{}\" is not a number", name))),
+        None => Err(invalid(format!("missing \"{}\"", name))),
+    };
+    let value = |name: &str| number(view_section, name);
+
+    let view_state = ViewState {
+        angle_x: value("angle_x")?,
+        angle_y: value("angle_y")?,
+        angle_z: value("angle_z")?,
+        c_angle_x: value("c_angle_x")?,
+        c_angle_y: value("c_angle_y")?,
+        c_angle_z: value("c_angle_z")?,
+        l_angle_x: value("l_angle_x")?,
+        l_angle_y: value("l_angle_y")?,
+        l_angle_z: value("l_angle_z")?,
+        camera_x: value("camera_x")?,
+        camera_y: value("camera_y")?,
+        camera_z: value("camera_z")?,
+        ref_x: value("ref_x")?,
+        ref_y: value("ref_y")?,
+        ref_z: value("ref_z")?,
+        z_offset: value("z_offset")?,
+        scale: value("scale")?,
+    };
+    let value = |name: &str| number(light_section, name);
+    let light = Light {
+        x: value("x")?,
+        y: value("y")?,
+        z: value("z")?,
+        intensity: value("intensity")?,
+    };
+    Ok((view_state, light))
+}
+
+fn view_state_fields(view_state: &ViewState) -> [(&'static str, f32); 17] {
+    [
+        ("angle_x", view_state.angle_x),
+        ("angle_y", view_state.angle_y),
+        ("angle_z", view_state.angle_z),
+        ("c_angle_x", view_state.c_angle_x),
+        ("c_angle_y", view_state.c_angle_y),
+        ("c_angle_z", view_state.c_angle_z),
+        ("l_angle_x", view_state.l_angle_x),
+        ("l_angle_y", view_state.l_angle_y),
+        ("l_angle_z", view_state.l_angle_z),
+        ("camera_x", view_state.camera_x),
+        ("camera_y", view_state.camera_y),
+        ("camera_z", view_state.camera_z),
+        ("ref_x", view_state.ref_x),
+        ("ref_y", view_state.ref_y),
+        ("ref_z", view_state.ref_z),
+        ("z_offset", view_state.z_offset),
+        ("scale", view_state.scale),
+    ]
+}
+
+// Debug formatting prints the shortest digits that read back to the same f32, so views round-trip exactly.
+// JSON has no NaN or infinity, those are written as null
+fn fields(values: &[(&str, f32)]) -> String {
+    values
+        .iter()
+        .map(|(name, value)| match value.is_finite() {
+            true => format!("    \"{}\": {:?}", name, value),
+            false => format!("    \"{}\": null", name),
+        })
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::cpu_renderer::CpuRenderer;
+    use crate::helpers::test_helper::{test_light, test_view_state};
+
+    #[test]
+    fn sidecars_are_json_and_load_back_exactly() {
+        let dir = std::env::temp_dir().join("screenshot_sidecar");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut renderer = CpuRenderer::headless(4, 4);
+        let view_state = ViewState { angle_x: 0.1 + 0.2, camera_z: 1e-7, ref_x: -1234.5678, ..test_view_state() };
+        let light = test_light();
+        renderer.render(&view_state, &light).unwrap();
+        let path = save_screenshot(&dir, &mut renderer, &view_state, &light).unwrap();
+
+        let sidecar = path.with_extension("json");
+        let json = Json::parse(&std::fs::read_to_string(&sidecar).unwrap()).unwrap();
+        assert_eq!(json.get("backend"), Some(&Json::String(renderer.backend())));
+        let (loaded_view, loaded_light) = load_view(&sidecar).unwrap();
+        assert_eq!(view_state_fields(&loaded_view), view_state_fields(&view_state));
+        assert_eq!((loaded_light.x, loaded_light.y, loaded_light.z, loaded_light.intensity), (light.x, light.y, light.z, light.intensity));
+
+        // A broken view still saves as valid JSON, but is refused when loaded
+        let broken = ViewState { angle_y: f32::NAN, camera_z: f32::INFINITY, ..view_state };
+        let path = save_screenshot(&dir, &mut renderer, &broken, &light).unwrap();
+        let sidecar = path.with_extension("json");
+        let json = Json::parse(&std::fs::read_to_string(&sidecar).unwrap()).unwrap();
+        assert_eq!(json.get("view_state").and_then(|view| view.get("camera_z")), Some(&Json::Null));
+        let Err(error) = load_view(&sidecar) else { panic!("a NaN view loaded") };
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("\"angle_y\" was not a finite number"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
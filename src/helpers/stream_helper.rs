@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use bytemuck::Zeroable;
+use crate::graphics::operations::Operations;
+use crate::types::{pixel::Pixel, renderer::Renderer, view_state::ViewState};
+
+// Chunked assets are a directory of raw `Pixel` arrays, one file per grid cell, plus this index
+const INDEX_FILE: &str = "index.txt";
+const FLUSH_THRESHOLD: usize = 1 << 20;
+const MAX_LOADS_PER_UPDATE: usize = 4;
+
+type ChunkKey = (i32, i32, i32);
+
+#[derive(Clone, Copy)]
+struct ChunkInfo {
+    key: ChunkKey,
+    count: usize,
+    min: (f32, f32, f32),
+    max: (f32, f32, f32),
+}
+
+impl ChunkInfo {
+    fn file_name(&self) -> String {
+        format!("chunk_{}_{}_{}.bin", self.key.0, self.key.1, self.key.2)
+    }
+
+    fn bytes(&self) -> usize {
+        self.count * std::mem::size_of::<Pixel>()
+    }
+
+    fn distance_to(&self, p: (f32, f32, f32)) -> f32 {
+        let dx = (self.min.0 - p.0).max(p.0 - self.max.0).max(0.0);
+        let dy = (self.min.1 - p.1).max(p.1 - self.max.1).max(0.0);
+        let dz = (self.min.2 - p.2).max(p.2 - self.max.2).max(0.0);
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+}
+
+/// Splits pixels into cubic chunks on disk without holding the whole set in memory.
+pub struct ChunkWriter {
+    dir: PathBuf,
+    chunk_size: f32,
+    chunks: HashMap<ChunkKey, ChunkInfo>,
+    pending: HashMap<ChunkKey, Vec<Pixel>>,
+    pending_count: usize,
+}
+
+impl ChunkWriter {
+    pub fn new(dir: &Path, chunk_size: f32) -> io::Result<ChunkWriter> {
+        fs::create_dir_all(dir)?;
+        Ok(ChunkWriter {
+            dir: dir.to_path_buf(),
+            chunk_size,
+            chunks: HashMap::new(),
+            pending: HashMap::new(),
+            pending_count: 0,
+        })
+    }
+
+    pub fn push(&mut self, pixel: Pixel) -> io::Result<()> {
+        let key = (
+            (pixel.x / self.chunk_size).floor() as i32,
+            (pixel.y / self.chunk_size).floor() as i32,
+            (pixel.z / self.chunk_size).floor() as i32,
+        );
+        let position = (pixel.x, pixel.y, pixel.z);
+        let info = self.chunks.entry(key).or_insert_with(|| {
+            // Chunks start empty on disk, so a stale file from an earlier run must not be appended to
+            let info = ChunkInfo { key, count: 0, min: position, max: position };
+            let _ = fs::remove_file(self.dir.join(info.file_name()));
+            info
+        });
+        info.count += 1;
+        info.min = (info.min.0.min(pixel.x), info.min.1.min(pixel.y), info.min.2.min(pixel.z));
+        info.max = (info.max.0.max(pixel.x), info.max.1.max(pixel.y), info.max.2.max(pixel.z));
+
+        self.pending.entry(key).or_default().push(pixel);
+        self.pending_count += 1;
+        if self.pending_count >= FLUSH_THRESHOLD {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining pixels and the index, returning the number of chunks.
+    pub fn finish(mut self) -> io::Result<usize> {
+        self.flush()?;
+
+        let mut chunks: Vec<&ChunkInfo> = self.chunks.values().collect();
+        chunks.sort_by_key(|info| info.key);
+        let mut index = File::create(self.dir.join(INDEX_FILE))?;
+        for info in chunks {
+            writeln!(
+                index,
+                "{} {} {} {} {} {} {} {} {} {}",
+                info.key.0, info.key.1, info.key.2, info.count,
+                info.min.0, info.min.1, info.min.2,
+                info.max.0, info.max.1, info.max.2,
+            )?;
+        }
+        Ok(self.chunks.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for (key, pixels) in self.pending.drain() {
+            let file_name = self.chunks[&key].file_name();
+            let mut file = OpenOptions::new().create(true).append(true).open(self.dir.join(file_name))?;
+            file.write_all(bytemuck::cast_slice(&pixels))?;
+        }
+        self.pending_count = 0;
+        Ok(())
+    }
+}
+
+pub fn has_chunks(dir: &Path) -> bool {
+    dir.join(INDEX_FILE).is_file()
+}
+
+/// Keeps the chunks nearest to the camera resident within a memory budget and evicts the rest.
+/// Each chunk is its own renderer object, `first_id` plus its place in the index, so loading or
+/// evicting one leaves the others where they are. The pixels live only in the renderer, the
+/// streamer keeps which chunks it holds and their size in bytes.
+pub struct ChunkStreamer {
+    first_id: u32,
+    dir: PathBuf,
+    chunks: Vec<ChunkInfo>,
+    resident: BTreeMap<usize, usize>,
+    budget: usize,
+}
+
+impl ChunkStreamer {
+    pub fn open(dir: &Path, first_id: u32, budget: usize) -> io::Result<ChunkStreamer> {
+        let index = BufReader::new(File::open(dir.join(INDEX_FILE))?);
+        let mut chunks = Vec::new();
+        for line in index.lines() {
+            let line = line?;
+            let values: Vec<&str> = line.split_whitespace().collect();
+            if values.len() != 10 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid chunk index line: {}", line)));
+            }
+            let int = |i: usize| values[i].parse::<i32>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+            let float = |i: usize| values[i].parse::<f32>().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+            chunks.push(ChunkInfo {
+                key: (int(0)?, int(1)?, int(2)?),
+                count: values[3].parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                min: (float(4)?, float(5)?, float(6)?),
+                max: (float(7)?, float(8)?, float(9)?),
+            });
+        }
+
+        Ok(ChunkStreamer {
+            first_id,
+            dir: dir.to_path_buf(),
+            chunks,
+            resident: BTreeMap::new(),
+            budget,
+        })
+    }
+
+    /// Loads and evicts chunks for the current view, returning whether the renderer's pixels changed.
+    /// Only a few chunks are read per call so streaming is spread over several frames.
+    pub async fn update(&mut self, view_state: &ViewState, renderer: &mut dyn Renderer<'_>) -> io::Result<bool> {
+        let eye = eye_position(view_state);
+        let mut order: Vec<usize> = (0..self.chunks.len()).collect();
+        order.sort_by(|&a, &b| self.chunks[a].distance_to(eye).total_cmp(&self.chunks[b].distance_to(eye)));
+
+        let mut wanted = HashSet::new();
+        let mut used = 0;
+        for &i in &order {
+            let bytes = self.chunks[i].bytes();
+            if used + bytes > self.budget {
+                break;
+            }
+            used += bytes;
+            wanted.insert(i);
+        }
+
+        let evicted: Vec<usize> = self.resident.keys().filter(|i| !wanted.contains(i)).copied().collect();
+        for &i in &evicted {
+            self.resident.remove(&i);
+            renderer.remove_object(self.id(i));
+        }
+
+        let mut loaded = 0;
+        for &i in &order {
+            if loaded == MAX_LOADS_PER_UPDATE {
+                break;
+            }
+            if !wanted.contains(&i) || self.resident.contains_key(&i) {
+                continue;
+            }
+            let pixels = self.read_chunk(i).await?;
+            self.resident.insert(i, pixels.len() * std::mem::size_of::<Pixel>());
+            renderer.add_object(self.id(i), pixels);
+            loaded += 1;
+        }
+
+        Ok(!evicted.is_empty() || loaded > 0)
+    }
+
+    /// Reads the resident chunks again for a renderer that replaced the previous one.
+    pub async fn resend(&self, renderer: &mut dyn Renderer<'_>) -> io::Result<()> {
+        for &i in self.resident.keys() {
+            renderer.update_object(self.id(i), self.read_chunk(i).await?);
+        }
+        Ok(())
+    }
+
+    fn id(&self, chunk: usize) -> u32 {
+        self.first_id + chunk as u32
+    }
+
+    async fn read_chunk(&self, chunk: usize) -> io::Result<Vec<Pixel>> {
+        let info = &self.chunks[chunk];
+        let bytes = async_std::fs::read(self.dir.join(info.file_name())).await?;
+        if bytes.len() != info.bytes() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("chunk {} does not match the index", info.file_name())));
+        }
+
+        let mut pixels = vec![Pixel::zeroed(); info.count];
+        bytemuck::cast_slice_mut::<Pixel, u8>(&mut pixels).copy_from_slice(&bytes);
+        for pixel in pixels.iter_mut() {
+            pixel.id = self.id(chunk);
+        }
+        Ok(pixels)
+    }
+}
+
+/// Position of the camera in the untransformed space of the pixels.
+fn eye_position(view_state: &ViewState) -> (f32, f32, f32) {
+    let (x, y, z) = Operations::rotate_inverse(
+        (-view_state.camera_x, -view_state.camera_y, 0.0),
+        (-view_state.c_angle_x, -view_state.c_angle_y, -view_state.c_angle_z)
+    );
+    Operations::rotate_inverse(
+        (x - view_state.ref_x, y - view_state.ref_y, z + view_state.camera_z - view_state.ref_z),
+        (view_state.angle_x, view_state.angle_y, view_state.angle_z)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use crate::graphics::cpu_renderer::CpuRenderer;
+    use crate::helpers::test_helper::test_view_state;
+
+    #[test]
+    fn written_chunks_stream_in_nearest_first_within_the_budget() {
+        let dir = std::env::temp_dir().join("stream_round_trip");
+        let _ = fs::remove_dir_all(&dir);
+        // Eight chunks of four pixels in a row going away from the camera along z
+        let mut writer = ChunkWriter::new(&dir, 10.0).unwrap();
+        for k in 0..8 {
+            for corner in 0..4 {
+                let pixel = Pixel { id: 0, x: 1.0 + corner as f32, y: 1.0, z: -2.0 - corner as f32 - 10.0 * k as f32, r: 1.0, g: 1.0, b: 1.0, a: 1.0, size_factor: 1.0 };
+                writer.push(pixel).unwrap();
+            }
+        }
+        assert_eq!(writer.finish().unwrap(), 8);
+
+        let budget = 3 * 4 * std::mem::size_of::<Pixel>();
+        let mut streamer = ChunkStreamer::open(&dir, 10, budget).unwrap();
+        let mut renderer = CpuRenderer::headless(8, 8);
+        let mut view_state = ViewState { angle_x: 0.0, angle_y: 0.0, camera_z: 100.0, ..test_view_state() };
+        let keys = |streamer: &ChunkStreamer| streamer.resident.keys().map(|&i| streamer.chunks[i].key.2).collect::<Vec<i32>>();
+
+        assert!(task::block_on(streamer.update(&view_state, &mut renderer)).unwrap());
+        assert_eq!(keys(&streamer), [-3, -2, -1]);
+        assert_eq!(renderer.pixel_count(), 12);
+        assert_eq!(streamer.resident.values().sum::<usize>(), budget);
+        assert!(!task::block_on(streamer.update(&view_state, &mut renderer)).unwrap());
+
+        for &i in streamer.resident.keys() {
+            let info = streamer.chunks[i];
+            let pixels = task::block_on(streamer.read_chunk(i)).unwrap();
+            assert_eq!(pixels.len(), info.count);
+            assert!(pixels.iter().all(|pixel| {
+                pixel.id == 10 + i as u32
+                    && (info.min.0..=info.max.0).contains(&pixel.x)
+                    && (info.min.2..=info.max.2).contains(&pixel.z)
+                    && (pixel.z / 10.0).floor() as i32 == info.key.2
+            }));
+        }
+
+        // Behind the far end the other three chunks are nearest, and the first ones are evicted
+        view_state.camera_z = -300.0;
+        assert!(task::block_on(streamer.update(&view_state, &mut renderer)).unwrap());
+        assert_eq!(keys(&streamer), [-8, -7, -6]);
+        assert_eq!(renderer.pixel_count(), 12);
+        assert_eq!(streamer.resident.values().sum::<usize>(), budget);
+
+        // A new renderer gets the resident chunks read back from disk
+        let mut replacement = CpuRenderer::headless(8, 8);
+        task::block_on(streamer.resend(&mut replacement)).unwrap();
+        assert_eq!(replacement.pixel_count(), 12);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,52 +1,33 @@
 #![allow(dead_code)]
-use crate::types::pixel::Pixel;
-
-pub fn generate_cube_pixels(id: u32, iters: usize, size: f32) -> (Vec<Pixel>, usize) {
-    let colors = [
-        [1.0, 0.0, 0.0, 1.0],
-        [0.0, 1.0, 0.0, 1.0],
-        [0.0, 0.0, 1.0, 1.0],
-        [1.0, 1.0, 0.0, 1.0],
-        [1.0, 0.0, 1.0, 1.0],
-        [0.0, 1.0, 1.0, 1.0],
-    ];
-
-    let step = (iters as f32).cbrt().floor() as usize;
-    let step_size = size / step as f32;
-
-    let mut pixels = Vec::new();
-    let mut count = 0;
-
-    for i in 0..step {
-        for j in 0..step {
-            for k in 0..step {
-                let is_face = i == 0 || i == step - 1 || j == 0 || j == step - 1 || k == 0 || k == step - 1;
-
-                if !is_face {
-                    continue;
-                }
-
-                let pos_x = i as f32 * step_size - size / 2.0 + step_size / 2.0;
-                let pos_y = j as f32 * step_size - size / 2.0 + step_size / 2.0;
-                let pos_z = k as f32 * step_size - size / 2.0 + step_size / 2.0;
-
-                let color = colors[(i + j + k) % colors.len()];
-
-                pixels.push(Pixel {
-                    id,
-                    x: pos_x + 150.0,
-                    y: pos_y + 50.0,
-                    z: pos_z - 10.0,
-                    r: color[0],
-                    g: color[1],
-                    b: color[2],
-                    a: color[3],
-                    size_factor: step_size,
-                });
-                count += 1;
-            }
-        }
+use crate::types::{light::Light, view_state::ViewState};
+
+pub fn test_view_state() -> ViewState {
+    ViewState {
+        angle_x: 0.5,
+        angle_y: 0.5,
+        angle_z: 0.0,
+        c_angle_x: 0.0,
+        c_angle_y: 0.0,
+        c_angle_z: 0.0,
+        l_angle_x: 0.0,
+        l_angle_y: 0.0,
+        l_angle_z: 0.0,
+        camera_x: 0.0,
+        camera_y: 0.0,
+        camera_z: 100.0,
+        ref_x: 0.0,
+        ref_y: 0.0,
+        ref_z: 0.0,
+        z_offset: 10.0,
+        scale: 300.0,
     }
+}
 
-    (pixels, count)
+pub fn test_light() -> Light {
+    Light {
+        x: 0.0,
+        y: 0.0,
+        z: 100.0,
+        intensity: 120.0,
+    }
 }
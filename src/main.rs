@@ -2,161 +2,551 @@ mod types;
 mod graphics;
 mod events;
 mod helpers;
+mod scene;
+mod camera;
+mod cli;
 
 use std::time::{Duration, Instant};
 use async_std::print;
 use async_std::task;
 use graphics::cpu_renderer::CpuRenderer;
-use graphics::gpu_renderer::GpuRenderer;
-use regex::Regex;
+use graphics::debug_overlay::{DebugOverlay, OverlayInfo};
+use graphics::gpu_renderer::{AdapterOptions, GpuRenderer};
 use types::renderer::Renderer;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 
-use types::{view_state::ViewState, light::Light, event_callback::EventCallback};
-use events::event_handler::EventHandler;
+use types::{engine_error::EngineError, event_callback::EventCallback, render_settings::RenderSettings};
+use events::{event_handler::EventHandler, input_map::InputMap};
+use helpers::export_helper::ExportSettings;
+use helpers::stream_helper::{ChunkStreamer, ChunkWriter};
+use helpers::generator_helper::{Color, Generator, Transform};
+use scene::{bookmarks::{Bookmarks, Transition, BOOKMARK_SLOTS}, camera_path::{CameraPath, PathPlayer, PathRecorder}, editor::Editor};
+use camera::{first_person::FirstPersonController, fly::FlyController, orbit::OrbitController};
+use cli::{config::Config, options::{Backend, Command, Options}};
+use types::camera_controller::CameraController;
 
-const DEFAULT_WIDTH: u32 = 800;
-const DEFAULT_HEIGHT: u32 = 600;
-const SCALE: f32 = 1000.0;
+const SCREENSHOT_DIR: &str = "screenshots";
+const CHUNK_DIR: &str = "chunks";
+const PARITY_DIR: &str = "parity";
+const BENCH_REPORT: &str = "bench.json";
 
-const FPS: u32 = 23;
-const FRAME_DURATION: Duration = Duration::from_millis(1000 / FPS as u64);
+const STREAM_CHUNK_SIZE: f32 = 10.0;
+
+const EXPORT_FPS: f32 = 30.0;
+
+const MAX_DELTA_TIME: f32 = 0.25;
 
 #[async_std::main]
 async fn main() {
-    let mut gpu_enabled = true;
-    let mut framerate_log = false;
-    let mut fullscreen = false;
-    let mut width: Option<u32> = None;
-    let mut height: Option<u32> = None;
+    let args: Vec<String> = env::args().collect();
+    let program = args.first()
+        .and_then(|arg| Path::new(arg).file_name())
+        .map_or(String::from("perfectengine"), |name| name.to_string_lossy().into_owned());
+    let options = match Options::parse(&args[1.min(args.len())..]) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {}\n\nRun '{} --help' for usage.", error, program);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        print!("{}", cli::options::help(&program, options.command)).await;
+        return;
+    }
+    let config = match Config::resolve(&options) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(2);
+        }
+    };
+    if options.print_config {
+        print!("{}", config.to_toml()).await;
+        return;
+    }
 
-    let width_regex = Regex::new(r"^w=(\d+)$").unwrap();
-    let height_regex = Regex::new(r"^h=(\d+)$").unwrap();
+    let result = match options.command {
+        Command::View => view(options, config).await,
+        Command::Render => render(options, config).await,
+        Command::Convert => convert(options).await,
+        Command::Info => info(options, config).await,
+        Command::Parity => parity(options, config).await,
+        Command::Bench => bench(options, config).await,
+    };
+    if let Err(error) = result {
+        eprintln!("\nerror: {}", error);
+        process::exit(1);
+    }
+}
 
-    let args: Vec<String> = env::args().collect();
-    for i in 1..args.len() {
-        if let Some(arg) = args.get(i) {
-            match arg.as_str() {
-                "gpu" => gpu_enabled = true,
-                "cpu" => gpu_enabled = false,
-                "framerate" => framerate_log = true,
-                "fullscreen" => fullscreen = true,
-                _ if width_regex.is_match(arg) => {
-                    if let Some(caps) = width_regex.captures(arg) {
-                        width = caps.get(1).map(|m| m.as_str().parse().unwrap());
-                    }
-                }
-                _ if height_regex.is_match(arg) => {
-                    if let Some(caps) = height_regex.captures(arg) {
-                        height = caps.get(1).map(|m| m.as_str().parse().unwrap());
-                    }
+/// Opens the scene in a window and runs the interactive loop.
+async fn view(options: Options, config: Config) -> Result<(), EngineError> {
+    let sdl_context = sdl2::init().map_err(EngineError::Init)?;
+    let video_subsystem = sdl_context.video().map_err(EngineError::Init)?;
+    let display_mode = video_subsystem.desktop_display_mode(0).map_err(EngineError::Init)?;
+
+    // Fullscreen takes the size of the screen unless one is given on the command line
+    let fullscreen = config.window.fullscreen;
+    let width = if fullscreen && options.width.is_none() { display_mode.w as u32 } else { config.window.width };
+    let height = if fullscreen && options.height.is_none() { display_mode.h as u32 } else { config.window.height };
+
+    let mut editor = Editor::new();
+    let pixel_count = build_scene(&mut editor, &options.scene, options.stream.is_none()).await?;
+
+    let mut streamer = open_streamer(options.stream.as_deref(), &config).await?;
+
+    let event_pump = sdl_context.event_pump().map_err(EngineError::Init)?;
+    let mut input_map = InputMap::new();
+    input_map.acceleration = config.input.acceleration;
+    input_map.damping = config.input.damping;
+    input_map.smoothing = config.input.smoothing;
+    let bindings_file = &config.input.bindings;
+    if bindings_file.is_file() {
+        input_map.load(bindings_file).map_err(EngineError::asset(bindings_file))?;
+    }
+    let mut event_handler = EventHandler::new(event_pump, input_map, sdl_context.game_controller().ok());
+
+    let mut view_state = config.view_state;
+    let mut cameras: Vec<Box<dyn CameraController>> = vec![
+        Box::new(FlyController::new(&view_state)),
+        Box::new(OrbitController::new(&view_state)),
+        Box::new(FirstPersonController::new(&view_state)),
+    ];
+    let mut camera_index = 0;
+    let mut recorder: Option<PathRecorder> = None;
+    let mut player: Option<PathPlayer> = None;
+    let mut light = config.light;
+    let bookmarks_file = Bookmarks::path_for(&options.scene);
+    let mut bookmarks = if bookmarks_file.is_file() { Bookmarks::load(&bookmarks_file).map_err(EngineError::asset(&bookmarks_file))? } else { Bookmarks::new() };
+    let mut transition: Option<Transition> = None;
+    // Bookmark 0 doubles as the view the scene opens with
+    if let Some(home) = bookmarks.get(0) {
+        (view_state, light) = (home.view_state, home.light);
+        cameras[camera_index].attach(&view_state);
+    }
+    if let Some(view_file) = &options.view {
+        (view_state, light) = helpers::screenshot_helper::load_view(view_file).map_err(EngineError::asset(view_file))?;
+        cameras[camera_index].attach(&view_state);
+    }
+
+    let screenshot_dir = options.output.clone().unwrap_or_else(|| PathBuf::from(SCREENSHOT_DIR));
+    let fps = config.window.fps;
+    // A cap of 0 leaves the frame rate to vsync
+    let frame_duration = if fps > 0.0 { Duration::from_secs_f32(1.0 / fps) } else { Duration::ZERO };
+
+    println!("\nFULLSCREEN: {}\t\tWIDTH: {}\t\tHEIGHT: {}", fullscreen, width, height);
+    println!("FPS LIMIT: {:5}\t\tPIXEL COUNT: {:10}", fps, pixel_count);
+    let mut backend = config.backend;
+    let mut window_size = (width, height);
+    let mut switched = false;
+    let mut overlay = DebugOverlay::new();
+    let mut render_settings = RenderSettings::default();
+    // Each pass opens a window with a renderer for the backend and runs until quitting or switching backends
+    'backends: loop {
+        let window = create_window(&video_subsystem, window_size, fullscreen)?;
+        let texture_creator;
+        let created: Result<Box<dyn Renderer<'_>>, EngineError> = if backend == Backend::Surface {
+            GpuRenderer::with_surface(window, config.window.vsync, &config.adapter).await.map(|renderer| Box::new(renderer) as Box<dyn Renderer<'_>>)
+        } else {
+            let canvas_builder = window.into_canvas();
+            let canvas_builder = if config.window.vsync { canvas_builder.present_vsync() } else { canvas_builder };
+            let canvas = canvas_builder.build().map_err(|error| EngineError::Window(error.to_string()))?;
+            texture_creator = canvas.texture_creator();
+            if backend == Backend::Gpu {
+                GpuRenderer::new(canvas, &texture_creator, &config.adapter).await.map(|renderer| Box::new(renderer) as Box<dyn Renderer<'_>>)
+            } else {
+                CpuRenderer::new(canvas, &texture_creator).map(|renderer| Box::new(renderer) as Box<dyn Renderer<'_>>)
+            }
+        };
+        let mut renderer = match created {
+            Ok(renderer) => renderer,
+            // The failed attempt took the window with it, the next pass opens a new one
+            Err(error) if backend != Backend::Cpu && (config.fallback || switched) => {
+                println!("\nGPU RENDERER UNAVAILABLE, FALLING BACK TO CPU: {}", error);
+                backend = Backend::Cpu;
+                continue 'backends;
+            },
+            Err(error) => return Err(error),
+        };
+
+        // A new renderer starts out empty
+        editor.resync();
+        if let Some(streamer) = &streamer {
+            streamer.resend(renderer.as_mut()).await?;
+        }
+        renderer.set_highlight(editor.selected_id());
+        renderer.set_render_settings(render_settings);
+        let backend_name = renderer.backend();
+        println!("\nBACKEND: {}", backend_name);
+
+        let mut last_frame = Instant::now();
+        loop {
+            let process_start = Instant::now();
+            // Movement follows the time that passed, capped so a stalled frame does not make the camera jump
+            let delta_time = (process_start - last_frame).as_secs_f32().min(MAX_DELTA_TIME);
+            last_frame = process_start;
+
+            for event_callback in event_handler.handle_events(&mut view_state, &mut light, &mut editor, cameras[camera_index].as_mut(), delta_time) {
+                match event_callback {
+                    EventCallback::Quit => break 'backends,
+                    EventCallback::Resized(w, h) => {
+                        renderer.resize(w, h)?;
+                        window_size = (w, h);
+                        view_state.scale = config.view_state.scale * (w as f32 / width as f32).min(h as f32 / height as f32);
+                    },
+                    EventCallback::Pick(x, y) => {
+                        let pick = renderer.pick(x, y);
+                        if let Some(pick) = &pick {
+                            println!("\nPICKED OBJECT: {:5}\t\tPIXEL: {:10}\t\tPOSITION: ({:.1}, {:.1}, {:.1})\t\tDEPTH: {:.1}",
+                                pick.object_id, pick.pixel_index, pick.x, pick.y, pick.z, pick.depth);
+                        }
+                        editor.select(pick.map(|pick| pick.object_id));
+                        renderer.set_highlight(editor.selected_id());
+                    },
+                    EventCallback::CycleCamera => {
+                        camera_index = (camera_index + 1) % cameras.len();
+                        cameras[camera_index].attach(&view_state);
+                        println!("\nCAMERA: {}", cameras[camera_index].name());
+                    },
+                    EventCallback::RecordPath => {
+                        match recorder.take() {
+                            Some(recording) => {
+                                recording.finish(&view_state, &light).save(&options.path).map_err(EngineError::asset(&options.path))?;
+                                println!("\nSAVED CAMERA PATH: {}", options.path.display());
+                            },
+                            None => {
+                                recorder = Some(PathRecorder::new());
+                                println!("\nRECORDING CAMERA PATH");
+                            },
+                        }
+                    },
+                    EventCallback::PlayPath => {
+                        player = match player {
+                            Some(_) => None,
+                            None => Some(PathPlayer::new(CameraPath::load(&options.path).map_err(EngineError::asset(&options.path))?)),
+                        };
+                        transition = None;
+                        cameras[camera_index].attach(&view_state);
+                    },
+                    EventCallback::Screenshot => {
+                        let path = helpers::screenshot_helper::save_screenshot(&screenshot_dir, renderer.as_mut(), &view_state, &light)?;
+                        println!("\nSAVED SCREENSHOT: {}", path.display());
+                    },
+                    EventCallback::Bookmark(slot) => {
+                        match bookmarks.get(slot) {
+                            Some(bookmark) => {
+                                player = None;
+                                transition = Some(Transition::new(&view_state, &light, bookmark));
+                            },
+                            None => println!("\nNO BOOKMARK: {}", slot),
+                        }
+                    },
+                    EventCallback::SaveBookmark(slot) => {
+                        bookmarks.set(slot, &view_state, &light);
+                        bookmarks.save(&bookmarks_file).map_err(EngineError::asset(&bookmarks_file))?;
+                        println!("\nSAVED BOOKMARK: {}", slot);
+                    },
+                    EventCallback::SwitchBackend => {
+                        backend = match backend {
+                            Backend::Gpu => Backend::Surface,
+                            Backend::Surface => Backend::Cpu,
+                            Backend::Cpu => Backend::Gpu,
+                        };
+                        switched = true;
+                        continue 'backends;
+                    },
+                    EventCallback::ToggleOverlay => {
+                        overlay.visible = !overlay.visible;
+                        // Visible and culled pixels are only counted while they are shown
+                        render_settings.stats = overlay.visible;
+                        renderer.set_render_settings(render_settings);
+                        if !overlay.visible {
+                            renderer.set_overlay(None);
+                        }
+                    },
+                    EventCallback::ToggleShadows => {
+                        render_settings.shadows = !render_settings.shadows;
+                        renderer.set_render_settings(render_settings);
+                        println!("\nSHADOWS: {}", render_settings.shadows);
+                    },
+                    EventCallback::ToggleLighting => {
+                        render_settings.lighting = !render_settings.lighting;
+                        renderer.set_render_settings(render_settings);
+                        println!("\nLIGHTING: {}", render_settings.lighting);
+                    },
+                    EventCallback::CycleDebugView => {
+                        render_settings.debug_view = render_settings.debug_view.next();
+                        renderer.set_render_settings(render_settings);
+                        println!("\nDEBUG VIEW: {}", render_settings.debug_view.name());
+                    },
+                    EventCallback::SaveScene => {
+                        editor.save_scene(&options.scene).map_err(EngineError::asset(&options.scene))?;
+                        println!("\nSAVED SCENE: {}", options.scene.display());
+                    },
+                    EventCallback::ControllerConnected(name) => println!("\nCONTROLLER CONNECTED: {}", name),
+                    EventCallback::ControllerDisconnected(name) => println!("\nCONTROLLER DISCONNECTED: {}", name),
                 }
-                _ => panic!("Invalid argument: {}. Please use 'framerate', 'fullscreen', 'w={{}}', or 'h={{}}'.", arg),
+            }
+
+            editor.sync(renderer.as_mut());
+            if let Some(streamer) = streamer.as_mut() {
+                streamer.update(&view_state, renderer.as_mut()).await?;
+            }
+
+            if let Some(recording) = recorder.as_mut() {
+                recording.record(delta_time, &view_state, &light);
+            }
+            if let Some(playing) = player.as_mut() && !playing.advance(delta_time, &mut view_state, &mut light) {
+                // The camera controller carries on from where the path ended
+                player = None;
+                cameras[camera_index].attach(&view_state);
+            }
+            if let Some(moving) = transition.as_mut() && !moving.advance(delta_time, &mut view_state, &mut light) {
+                transition = None;
+                cameras[camera_index].attach(&view_state);
+            }
+
+            if overlay.visible {
+                renderer.set_overlay(Some(overlay.draw(&OverlayInfo {
+                    backend: &backend_name,
+                    width: window_size.0,
+                    height: window_size.1,
+                    pixel_count: renderer.pixel_count(),
+                    stats: renderer.frame_stats(),
+                    view_state: &view_state,
+                    light: &light,
+                    settings: render_settings,
+                })));
+            }
+            renderer.render(&view_state, &light)?;
+
+            let process_duration = process_start.elapsed();
+            overlay.record(process_duration.as_secs_f32() * 1000.0);
+            if options.framerate {
+                let seconds = process_duration.as_secs_f32();
+                print!("\rFRAME TIME: {:7.2}ms\t\tFRAME RATE: {:7.1}", seconds * 1000.0, 1.0 / seconds.max(f32::EPSILON)).await;
+            }
+            if process_duration < frame_duration {
+                task::sleep(frame_duration - process_duration).await;
             }
         }
     }
+    Ok(())
+}
 
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let display_mode = video_subsystem.desktop_display_mode(0).unwrap();
+fn create_window(video_subsystem: &sdl2::VideoSubsystem, (width, height): (u32, u32), fullscreen: bool) -> Result<sdl2::video::Window, EngineError> {
+    let mut builder = video_subsystem.window("Perfect Engine", width, height);
+    builder.vulkan();
+    if fullscreen {
+        builder.fullscreen();
+    } else {
+        builder.position_centered().resizable();
+    }
+    builder.build().map_err(|error| EngineError::Window(error.to_string()))
+}
 
-    let width = if let Some(w) = width {w} else { if fullscreen { display_mode.w as u32 } else {DEFAULT_WIDTH} };
-    let height = if let Some(h) = height {h} else { if fullscreen { display_mode.h as u32 } else {DEFAULT_HEIGHT} };
+/// Renders the camera path offscreen at the requested size, without opening a window.
+async fn render(options: Options, config: Config) -> Result<(), EngineError> {
+    let width = options.width.unwrap_or(config.window.width);
+    let height = options.height.unwrap_or(config.window.height);
+    // Frames are rendered offscreen, the surface backend renders like the gpu one
+    let mut renderer: Box<dyn Renderer<'static>> = match config.backend {
+        Backend::Cpu => Box::new(CpuRenderer::headless(width, height)),
+        Backend::Gpu | Backend::Surface => match GpuRenderer::headless(width, height, &config.adapter).await {
+            Ok(renderer) => Box::new(renderer),
+            Err(error) if config.fallback => {
+                println!("GPU RENDERER UNAVAILABLE, FALLING BACK TO CPU: {}", error);
+                Box::new(CpuRenderer::headless(width, height))
+            },
+            Err(error) => return Err(error),
+        },
+    };
+    println!("BACKEND: {}", renderer.backend());
+    let mut editor = Editor::new();
+    build_scene(&mut editor, &options.scene, options.stream.is_none()).await?;
+    editor.sync(renderer.as_mut());
+    let mut streamer = open_streamer(options.stream.as_deref(), &config).await?;
 
-    let window = if fullscreen {
-        video_subsystem.window("Perfect Engine", width, height)
-        .vulkan()
-        .fullscreen()
-        .build()
-        .unwrap()
-    } else {
-        video_subsystem.window("Perfect Engine", width, height)
-        .vulkan()
-        .position_centered()
-        .resizable()
-        .build()
-        .unwrap()
+    let mut view_state = config.view_state;
+    if let Some(view_file) = &options.view {
+        view_state = helpers::screenshot_helper::load_view(view_file).map_err(EngineError::asset(view_file))?.0;
+    }
+    // The configured scale is for the window size, frames of another size are scaled to fit the same view
+    view_state.scale = config.view_state.scale * (width as f32 / config.window.width as f32).min(height as f32 / config.window.height as f32);
+    let path = CameraPath::load(&options.path).map_err(EngineError::asset(&options.path))?;
+    let settings = ExportSettings { dir: options.output, encoder: options.encoder, fps: options.fps.unwrap_or(EXPORT_FPS) };
+    let frames = helpers::export_helper::export_path(renderer.as_mut(), streamer.as_mut(), &path, &view_state, &settings, width, height).await?;
+    println!("RENDERED {} FRAMES AT {}x{}", frames, width, height);
+    Ok(())
+}
+
+/// Opens the chunks the model is streamed from, when streaming.
+async fn open_streamer(stream_dir: Option<&Path>, config: &Config) -> Result<Option<ChunkStreamer>, EngineError> {
+    let Some(stream_dir) = stream_dir else {
+        return Ok(None);
     };
+    // The model is split into chunks on the first run and streamed around the camera afterwards
+    if !helpers::stream_helper::has_chunks(stream_dir) {
+        convert_model(stream_dir).await?;
+    }
+    Ok(Some(ChunkStreamer::open(stream_dir, 2, config.stream.budget_bytes()).map_err(EngineError::asset(stream_dir))?))
+}
+
+/// Splits the model into chunks that `view --stream` and `render --stream` can load around the camera.
+async fn convert(options: Options) -> Result<(), EngineError> {
+    let dir = options.output.unwrap_or_else(|| PathBuf::from(CHUNK_DIR));
+    let pixel_count = convert_model(&dir).await?;
+    println!("CONVERTED {} PIXELS INTO {}", pixel_count, dir.display());
+    Ok(())
+}
+
+/// Prints what the engine would run on and what the scene's files hold, for bug reports.
+async fn info(options: Options, config: Config) -> Result<(), EngineError> {
+    println!("VERSION:     {}", env!("CARGO_PKG_VERSION"));
+    let config_file = options.config.clone().unwrap_or_else(|| PathBuf::from(cli::config::CONFIG_FILE));
+    println!("CONFIG:      {}", if config_file.is_file() { config_file.display().to_string() } else { String::from("defaults") });
+    match GpuRenderer::headless(1, 1, &config.adapter).await {
+        Ok(renderer) => println!("GPU:         {}", renderer.backend()),
+        Err(error) => println!("GPU:         unavailable, only the cpu backend works ({})", error),
+    }
 
-    let canvas = window.into_canvas().present_vsync().build().unwrap();
-    let texture_creator = canvas.texture_creator();
-    let mut renderer: Box<dyn Renderer<'_>> = if gpu_enabled {
-        Box::new(GpuRenderer::new(canvas, &texture_creator).await)
+    if options.scene.is_file() {
+        let contents = fs::read_to_string(&options.scene).map_err(EngineError::asset(&options.scene))?;
+        let objects = contents.lines().filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#')).count();
+        println!("SCENE:       {} ({} objects)", options.scene.display(), objects);
     } else {
-        Box::new(CpuRenderer::new(canvas, &texture_creator))
-    };
+        println!("SCENE:       {} (not saved yet)", options.scene.display());
+    }
 
-    let mut pixel_count = 0;
-    let (pixels, count) = helpers::test_helper::generate_cube_pixels(1, 1000000, 23.0);
-    pixel_count += count;
-    renderer.load_pixels(pixels);
+    let bookmarks_file = Bookmarks::path_for(&options.scene);
+    if bookmarks_file.is_file() {
+        let bookmarks = Bookmarks::load(&bookmarks_file).map_err(EngineError::asset(&bookmarks_file))?;
+        let slots: Vec<String> = (0..BOOKMARK_SLOTS).filter(|&slot| bookmarks.get(slot).is_some()).map(|slot| slot.to_string()).collect();
+        println!("BOOKMARKS:   {} ({})", bookmarks_file.display(), slots.join(", "));
+    }
 
-    // Opens the .msh file generated by running "gmsh mesh_script.geo" in the command-line in "/helpers/model_helper" directory (after installing gmsh) 
-    // Output is big and takes time to generate
-    let (pixels, count) = helpers::model_helper::load_msh_file_with_texture(2).await;
-    pixel_count += count;
-    renderer.load_pixels(pixels);
-
-    // TODO:
-    // - Save pixels of an object without id in a JSON file
-    // - Save a scene as a list of id, filename, position, rotation of the objects in the scene in a JSON file
-    // - Construct each object by reading the properties stored in a scene and then load the pixels using the filename and id
-
-    let event_pump = sdl_context.event_pump().unwrap();
-    let mut event_handler = EventHandler::new(event_pump);
-
-    let mut view_state = ViewState { 
-        angle_x: 0.0,
-        angle_y: 0.0,
-        angle_z: 25.0,
-        c_angle_x: 0.0,
-        c_angle_y: 0.0,
-        c_angle_z: 0.0,
-        l_angle_x: 0.0,
-        l_angle_y: 0.0,
-        l_angle_z: 0.0,
-        camera_x: 0.0,
-        camera_y: 0.0,
-        camera_z: 700.0,
-        ref_x: 0.0,
-        ref_y: 0.0,
-        ref_z: 0.0,
-        z_offset: 50.0,
-        scale: SCALE,
-    };
-    let mut light = Light {
-        x: 0.0,
-        y: 0.0,
-        z: 700.0,
-        intensity: 500.0,
+    if options.path.is_file() {
+        let path = CameraPath::load(&options.path).map_err(EngineError::asset(&options.path))?;
+        println!("CAMERA PATH: {} ({} keyframes, {:.1}s)", options.path.display(), path.keyframes().len(), path.duration());
+    }
+    let bindings_file = &config.input.bindings;
+    if bindings_file.is_file() {
+        InputMap::new().load(bindings_file).map_err(EngineError::asset(bindings_file))?;
+        println!("BINDINGS:    {}", bindings_file.display());
+    }
+    println!("\n{}", config.to_toml());
+    Ok(())
+}
+
+/// Renders the reference scenes, and the scene from `--view` when given, through the cpu and gpu
+/// renderers, printing how much they differ and writing both frames with a diff image for each.
+async fn parity(options: Options, config: Config) -> Result<(), EngineError> {
+    let (reference_width, reference_height) = helpers::golden_helper::REFERENCE_SIZE;
+    let width = options.width.unwrap_or(reference_width);
+    let height = options.height.unwrap_or(reference_height);
+    let dir = options.output.clone().unwrap_or_else(|| PathBuf::from(PARITY_DIR));
+    // A software adapter still runs the same shaders when there is no GPU
+    let adapter = AdapterOptions { allow_software: true, ..config.adapter };
+
+    let mut scenes = Vec::new();
+    for scene in helpers::golden_helper::reference_scenes() {
+        let mut view_state = scene.view_state;
+        view_state.scale *= (width as f32 / reference_width as f32).min(height as f32 / reference_height as f32);
+        let mut cpu = CpuRenderer::headless(width, height);
+        let mut gpu = GpuRenderer::headless(width, height, &adapter).await?;
+        scene.load(&mut cpu);
+        scene.load(&mut gpu);
+        scenes.push((scene.name, cpu, gpu, view_state, scene.light));
+    }
+    if let Some(view_file) = &options.view {
+        let (view_state, light) = helpers::screenshot_helper::load_view(view_file).map_err(EngineError::asset(view_file))?;
+        let mut editor = Editor::new();
+        build_scene(&mut editor, &options.scene, true).await?;
+        let mut cpu = CpuRenderer::headless(width, height);
+        let mut gpu = GpuRenderer::headless(width, height, &adapter).await?;
+        editor.sync(&mut cpu);
+        editor.resync();
+        editor.sync(&mut gpu);
+        scenes.push(("scene", cpu, gpu, view_state, light));
+    }
+
+    let Some((_, _, first_gpu, _, _)) = scenes.first() else {
+        return Ok(());
     };
+    println!("GPU: {}\n", first_gpu.backend());
+    for (name, mut cpu, mut gpu, view_state, light) in scenes {
+        let report = helpers::parity_helper::compare_backends(name, &mut cpu, &mut gpu, &view_state, &light)?;
+        report.save(&dir)?;
+        println!("{}", report.summary());
+    }
+    println!("\nFRAMES AND DIFF IMAGES WRITTEN TO {}", dir.display());
+    Ok(())
+}
 
-    println!("\nFULLSCREEN:   {}\t\tWIDTH: {}\t\tHEIGHT: {}", fullscreen, width, height);
-    println!("FPS LIMIT: {:5}\t\tPIXEL COUNT: {:10}", FPS, pixel_count);
-    'running: loop {
-        let process_start = Instant::now();
-
-        let event_callback = event_handler.handle_events(&mut view_state, &mut light);
-        match event_callback {
-            Some(EventCallback::Quit) => break 'running,
-            Some(EventCallback::Resized(w, h)) => {
-                renderer.resize(w, h);
-                view_state.scale = SCALE * (w as f32 / width as f32).min(h as f32 / height as f32);
-            },
-            None => {}
+/// Times the fixed camera path over a generated scene of each requested size and writes the statistics as JSON.
+async fn bench(options: Options, config: Config) -> Result<(), EngineError> {
+    let width = options.width.unwrap_or(config.window.width);
+    let height = options.height.unwrap_or(config.window.height);
+    let output = options.output.clone().unwrap_or_else(|| PathBuf::from(BENCH_REPORT));
+
+    let mut backend = String::new();
+    let mut results = Vec::new();
+    for &pixel_count in &options.pixels {
+        // Benchmarks run headless, the surface backend is timed as the gpu one
+        let mut renderer: Box<dyn Renderer<'static>> = match config.backend {
+            Backend::Cpu => Box::new(CpuRenderer::headless(width, height)),
+            Backend::Gpu | Backend::Surface => Box::new(GpuRenderer::headless(width, height, &config.adapter).await?),
+        };
+        renderer.load_pixels(helpers::bench_helper::bench_scene(pixel_count));
+        backend = renderer.backend();
+
+        let result = helpers::bench_helper::run(renderer.as_mut(), options.frames, height)?;
+        println!("PIXELS: {:10}\t\tMIN: {:8.2}ms\t\tMEAN: {:8.2}ms\t\tP95: {:8.2}ms\t\tP99: {:8.2}ms",
+            result.pixel_count, result.frame.min, result.frame.mean, result.frame.p95, result.frame.p99);
+        for (name, stats) in &result.passes {
+            println!("  {:<20}\t\tMEAN: {:8.3}ms\t\tP95: {:8.3}ms", name, stats.mean, stats.p95);
         }
+        results.push(result);
+    }
 
-        // Experimental animation
-        // view_state.angle_y = (view_state.angle_y - 0.01).rem_euclid(2.0 * std::f32::consts::PI);
+    fs::write(&output, helpers::bench_helper::report_json(&backend, width, height, options.frames, &results)).map_err(EngineError::asset(&output))?;
+    println!("\nBACKEND: {}\t\tREPORT: {}", backend, output.display());
+    Ok(())
+}
 
-        renderer.render(&view_state, &light);
+async fn convert_model(dir: &Path) -> Result<usize, EngineError> {
+    let mut writer = ChunkWriter::new(dir, STREAM_CHUNK_SIZE).map_err(EngineError::asset(dir))?;
+    // Each node goes straight to its chunk, so models larger than memory can be converted
+    let pixel_count = helpers::model_helper::read_msh_file_with_texture(2, |pixel| writer.push(pixel).map_err(EngineError::asset(dir))).await?;
+    writer.finish().map_err(EngineError::asset(dir))?;
+    Ok(pixel_count)
+}
 
-        let process_duration = process_start.elapsed();
-        if framerate_log {
-            print!("\rFRAME TIME: {:4}ms\t\tFRAME RATE: {:4}", process_duration.as_millis(), 1000 / process_duration.as_millis()).await;
-        }
-        if process_duration < FRAME_DURATION {
-            task::sleep(FRAME_DURATION - process_duration).await;
-        }
+/// Adds the demo objects to the editor and applies the saved scene, leaving out the model when it is streamed instead.
+async fn build_scene(editor: &mut Editor, scene_file: &Path, with_model: bool) -> Result<usize, EngineError> {
+    let mut pixel_count = 0;
+    let cube = Generator {
+        transform: Transform::at(150.0, 50.0, -10.0),
+        color: Color::checker([1.0, 0.2, 0.2, 1.0], [0.2, 0.4, 1.0, 1.0], 4),
+        ..Generator::new(1, 19.0)
+    };
+    let pixels = cube.cuboid((23.0, 23.0, 23.0));
+    pixel_count += pixels.len();
+    editor.add_object(1, pixels);
+
+    // Opens the .msh file generated by running "gmsh mesh_script.geo" in the command-line in "/helpers/model_helper" directory (after installing gmsh) 
+    // Output is big and takes time to generate
+    if with_model {
+        let (pixels, count) = helpers::model_helper::load_msh_file_with_texture(2).await?;
+        pixel_count += count;
+        editor.add_object(2, pixels);
+    }
+
+    if scene_file.is_file() {
+        editor.load_scene(scene_file).map_err(EngineError::asset(scene_file))?;
     }
+    Ok(pixel_count)
 }
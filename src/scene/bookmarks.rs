@@ -0,0 +1,198 @@
+#![allow(dead_code)]
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::types::{light::Light, view_state::ViewState};
+
+pub const BOOKMARK_SLOTS: usize = 10;
+const TRANSITION_DURATION: f32 = 0.6;
+
+#[derive(Clone, Copy)]
+pub struct Bookmark {
+    pub view_state: ViewState,
+    pub light: Light,
+}
+
+/// Numbered snapshots of the whole view and light, kept in a file next to the scene they belong to.
+pub struct Bookmarks {
+    slots: [Option<Bookmark>; BOOKMARK_SLOTS],
+}
+
+impl Bookmarks {
+    pub fn new() -> Bookmarks {
+        Bookmarks { slots: [None; BOOKMARK_SLOTS] }
+    }
+
+    /// The bookmarks file of a scene, `scene.txt` keeping its bookmarks in `scene.bookmarks.txt`.
+    pub fn path_for(scene_file: &Path) -> PathBuf {
+        scene_file.with_extension("bookmarks.txt")
+    }
+
+    pub fn get(&self, slot: usize) -> Option<Bookmark> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    pub fn set(&mut self, slot: usize, view_state: &ViewState, light: &Light) {
+        self.slots[slot] = Some(Bookmark { view_state: *view_state, light: *light });
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::from("# slot angle_x angle_y angle_z c_angle_x c_angle_y c_angle_z l_angle_x l_angle_y l_angle_z camera_x camera_y camera_z ref_x ref_y ref_z z_offset scale light_x light_y light_z intensity\n");
+        for (slot, bookmark) in self.slots.iter().enumerate() {
+            let Some(Bookmark { view_state: v, light: l }) = bookmark else {
+                continue;
+            };
+            text += &format!(
+                "{} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {}\n",
+                slot, v.angle_x, v.angle_y, v.angle_z, v.c_angle_x, v.c_angle_y, v.c_angle_z,
+                v.l_angle_x, v.l_angle_y, v.l_angle_z, v.camera_x, v.camera_y, v.camera_z,
+                v.ref_x, v.ref_y, v.ref_z, v.z_offset, v.scale, l.x, l.y, l.z, l.intensity
+            );
+        }
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Bookmarks> {
+        let mut bookmarks = Bookmarks::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid bookmark line: {}", line));
+            let mut values = line.split_whitespace();
+            let slot: usize = values.next().and_then(|slot| slot.parse().ok()).filter(|&slot| slot < BOOKMARK_SLOTS).ok_or_else(invalid)?;
+            let values: Vec<f32> = values
+                .map(|value| value.parse().map_err(|_| invalid()))
+                .collect::<io::Result<_>>()?;
+            let [angle_x, angle_y, angle_z, c_angle_x, c_angle_y, c_angle_z, l_angle_x, l_angle_y, l_angle_z,
+                camera_x, camera_y, camera_z, ref_x, ref_y, ref_z, z_offset, scale, x, y, z, intensity] = values[..] else {
+                return Err(invalid());
+            };
+            bookmarks.slots[slot] = Some(Bookmark {
+                view_state: ViewState {
+                    angle_x, angle_y, angle_z,
+                    c_angle_x, c_angle_y, c_angle_z,
+                    l_angle_x, l_angle_y, l_angle_z,
+                    camera_x, camera_y, camera_z,
+                    ref_x, ref_y, ref_z,
+                    z_offset, scale,
+                },
+                light: Light { x, y, z, intensity },
+            });
+        }
+        Ok(bookmarks)
+    }
+}
+
+/// Eases the view and light from where they are to a bookmark, turning angles the short way round.
+pub struct Transition {
+    from: Bookmark,
+    to: Bookmark,
+    time: f32,
+}
+
+impl Transition {
+    pub fn new(view_state: &ViewState, light: &Light, to: Bookmark) -> Transition {
+        Transition {
+            from: Bookmark { view_state: *view_state, light: *light },
+            to,
+            time: 0.0,
+        }
+    }
+
+    /// Moves the view and light towards the bookmark, returning false once they have reached it.
+    pub fn advance(&mut self, delta_time: f32, view_state: &mut ViewState, light: &mut Light) -> bool {
+        self.time += delta_time;
+        let t = (self.time / TRANSITION_DURATION).min(1.0);
+        if t >= 1.0 {
+            *view_state = self.to.view_state;
+            *light = self.to.light;
+            return false;
+        }
+
+        // Smoothstep starts and ends the move at rest
+        let t = t * t * (3.0 - 2.0 * t);
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let turn = |a: f32, b: f32| a + ((b - a + PI).rem_euclid(2.0 * PI) - PI) * t;
+        let (a, b) = (&self.from.view_state, &self.to.view_state);
+        *view_state = ViewState {
+            angle_x: turn(a.angle_x, b.angle_x),
+            angle_y: turn(a.angle_y, b.angle_y),
+            angle_z: turn(a.angle_z, b.angle_z),
+            c_angle_x: turn(a.c_angle_x, b.c_angle_x),
+            c_angle_y: turn(a.c_angle_y, b.c_angle_y),
+            c_angle_z: turn(a.c_angle_z, b.c_angle_z),
+            l_angle_x: turn(a.l_angle_x, b.l_angle_x),
+            l_angle_y: turn(a.l_angle_y, b.l_angle_y),
+            l_angle_z: turn(a.l_angle_z, b.l_angle_z),
+            camera_x: lerp(a.camera_x, b.camera_x),
+            camera_y: lerp(a.camera_y, b.camera_y),
+            camera_z: lerp(a.camera_z, b.camera_z),
+            ref_x: lerp(a.ref_x, b.ref_x),
+            ref_y: lerp(a.ref_y, b.ref_y),
+            ref_z: lerp(a.ref_z, b.ref_z),
+            z_offset: lerp(a.z_offset, b.z_offset),
+            scale: lerp(a.scale, b.scale),
+        };
+        let (a, b) = (&self.from.light, &self.to.light);
+        *light = Light {
+            x: lerp(a.x, b.x),
+            y: lerp(a.y, b.y),
+            z: lerp(a.z, b.z),
+            intensity: lerp(a.intensity, b.intensity),
+        };
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view_state(angle_z: f32, camera_z: f32) -> ViewState {
+        ViewState {
+            angle_x: 0.1, angle_y: 0.2, angle_z,
+            c_angle_x: 0.0, c_angle_y: 0.3, c_angle_z: 0.0,
+            l_angle_x: 0.0, l_angle_y: 0.0, l_angle_z: 0.0,
+            camera_x: 10.0, camera_y: -5.0, camera_z,
+            ref_x: 0.0, ref_y: 0.0, ref_z: 0.0,
+            z_offset: 50.0, scale: 600.0,
+        }
+    }
+
+    #[test]
+    fn bookmarks_round_trip_through_the_file() {
+        let light = Light { x: 1.0, y: 2.0, z: 700.0, intensity: 0.1234567 };
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.set(3, &view_state(25.0, 700.0), &light);
+        let path = std::env::temp_dir().join("bookmarks_round_trip.txt");
+        bookmarks.save(&path).unwrap();
+
+        let loaded = Bookmarks::load(&path).unwrap();
+        let bookmark = loaded.get(3).unwrap();
+        assert!(loaded.get(0).is_none());
+        assert_eq!(bookmark.view_state.angle_z, 25.0);
+        assert_eq!(bookmark.view_state.camera_y, -5.0);
+        assert_eq!(bookmark.light.intensity, light.intensity);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn transition_turns_the_short_way_and_lands_on_the_bookmark() {
+        let light = Light { x: 0.0, y: 0.0, z: 700.0, intensity: 500.0 };
+        let target = Bookmark { view_state: view_state(2.0 * PI - 0.2, 300.0), light };
+        let (mut current, mut current_light) = (view_state(0.2, 700.0), light);
+        let mut transition = Transition::new(&current, &current_light, target);
+
+        assert!(transition.advance(TRANSITION_DURATION / 2.0, &mut current, &mut current_light));
+        assert!(current.angle_z.abs() < 1e-4);
+        assert!((current.camera_z - 500.0).abs() < 1e-3);
+
+        assert!(!transition.advance(TRANSITION_DURATION, &mut current, &mut current_light));
+        assert_eq!(current.angle_z, target.view_state.angle_z);
+        assert_eq!(current.camera_z, 300.0);
+    }
+}
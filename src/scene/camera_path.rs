@@ -0,0 +1,317 @@
+#![allow(dead_code)]
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::types::{camera_controller::Camera, light::Light, view_state::ViewState};
+
+// Recording keeps one keyframe per interval, interpolation fills in the rest on playback
+const RECORD_INTERVAL: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interpolation {
+    Linear,
+    CatmullRom,
+}
+
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f32,
+    pub camera: Camera,
+    pub light: Light,
+}
+
+/// Keyframed camera and light over time. Positions and the light follow the interpolation,
+/// camera orientations are always interpolated along the shortest arc.
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    interpolation: Interpolation,
+}
+
+impl CameraPath {
+    pub fn new(interpolation: Interpolation) -> CameraPath {
+        CameraPath {
+            keyframes: Vec::new(),
+            interpolation,
+        }
+    }
+
+    /// Adds a keyframe, which has to come after the ones added before.
+    pub fn push(&mut self, keyframe: Keyframe) {
+        self.keyframes.push(keyframe);
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// The camera and light at a time, holding the first and last keyframes outside the path.
+    pub fn sample(&self, time: f32) -> Option<(Camera, Light)> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        let next = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return Some((self.keyframes[0].camera, self.keyframes[0].light));
+        }
+        if next > last {
+            return Some((self.keyframes[last].camera, self.keyframes[last].light));
+        }
+
+        let i = next - 1;
+        let (k1, k2) = (&self.keyframes[i], &self.keyframes[next]);
+        let k0 = &self.keyframes[i.saturating_sub(1)];
+        let k3 = &self.keyframes[(next + 1).min(last)];
+        let t = ((time - k1.time) / (k2.time - k1.time).max(f32::EPSILON)).clamp(0.0, 1.0);
+
+        let interpolate = |p0: f32, p1: f32, p2: f32, p3: f32| match self.interpolation {
+            Interpolation::Linear => p1 + (p2 - p1) * t,
+            Interpolation::CatmullRom => catmull_rom(p0, p1, p2, p3, t),
+        };
+        let eye = (
+            interpolate(k0.camera.eye.0, k1.camera.eye.0, k2.camera.eye.0, k3.camera.eye.0),
+            interpolate(k0.camera.eye.1, k1.camera.eye.1, k2.camera.eye.1, k3.camera.eye.1),
+            interpolate(k0.camera.eye.2, k1.camera.eye.2, k2.camera.eye.2, k3.camera.eye.2),
+        );
+        let orientation = Quaternion::from_camera(&k1.camera).slerp(Quaternion::from_camera(&k2.camera), t);
+        let (yaw, pitch) = orientation.yaw_pitch();
+        let light = Light {
+            x: interpolate(k0.light.x, k1.light.x, k2.light.x, k3.light.x),
+            y: interpolate(k0.light.y, k1.light.y, k2.light.y, k3.light.y),
+            z: interpolate(k0.light.z, k1.light.z, k2.light.z, k3.light.z),
+            intensity: interpolate(k0.light.intensity, k1.light.intensity, k2.light.intensity, k3.light.intensity).max(0.0),
+        };
+        Some((Camera { eye, yaw, pitch }, light))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let interpolation = match self.interpolation {
+            Interpolation::Linear => "linear",
+            Interpolation::CatmullRom => "catmull_rom",
+        };
+        let mut text = format!("interpolation {}\n# time eye_x eye_y eye_z yaw pitch light_x light_y light_z intensity\n", interpolation);
+        for k in &self.keyframes {
+            text += &format!(
+                "{} {} {} {} {} {} {} {} {} {}\n",
+                k.time, k.camera.eye.0, k.camera.eye.1, k.camera.eye.2, k.camera.yaw, k.camera.pitch,
+                k.light.x, k.light.y, k.light.z, k.light.intensity
+            );
+        }
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &Path) -> io::Result<CameraPath> {
+        let mut camera_path = CameraPath::new(Interpolation::CatmullRom);
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid camera path line: {}", line));
+            match line.strip_prefix("interpolation ").map(str::trim) {
+                Some("linear") => camera_path.interpolation = Interpolation::Linear,
+                Some("catmull_rom") => camera_path.interpolation = Interpolation::CatmullRom,
+                Some(_) => return Err(invalid()),
+                None => {
+                    let values: Vec<f32> = line.split_whitespace()
+                        .map(|value| value.parse().map_err(|_| invalid()))
+                        .collect::<io::Result<_>>()?;
+                    let [time, eye_x, eye_y, eye_z, yaw, pitch, x, y, z, intensity] = values[..] else {
+                        return Err(invalid());
+                    };
+                    if time < camera_path.duration() {
+                        return Err(invalid());
+                    }
+                    camera_path.push(Keyframe {
+                        time,
+                        camera: Camera { eye: (eye_x, eye_y, eye_z), yaw, pitch },
+                        light: Light { x, y, z, intensity },
+                    });
+                }
+            }
+        }
+        Ok(camera_path)
+    }
+}
+
+/// Samples the camera and light while recording, at a fixed interval of recorded time.
+pub struct PathRecorder {
+    path: CameraPath,
+    time: f32,
+}
+
+impl PathRecorder {
+    pub fn new() -> PathRecorder {
+        PathRecorder {
+            path: CameraPath::new(Interpolation::CatmullRom),
+            time: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, delta_time: f32, view_state: &ViewState, light: &Light) {
+        if self.path.keyframes.is_empty() || self.time - self.path.duration() >= RECORD_INTERVAL {
+            self.path.push(Keyframe {
+                time: self.time,
+                camera: Camera::from_view_state(view_state),
+                light: *light,
+            });
+        }
+        self.time += delta_time;
+    }
+
+    pub fn finish(mut self, view_state: &ViewState, light: &Light) -> CameraPath {
+        self.path.push(Keyframe {
+            time: self.time,
+            camera: Camera::from_view_state(view_state),
+            light: *light,
+        });
+        self.path
+    }
+}
+
+/// Steps through a path in real time.
+pub struct PathPlayer {
+    path: CameraPath,
+    time: f32,
+}
+
+impl PathPlayer {
+    pub fn new(path: CameraPath) -> PathPlayer {
+        PathPlayer { path, time: 0.0 }
+    }
+
+    /// Moves the view's camera and the light along the path, returning false once it is over.
+    pub fn advance(&mut self, delta_time: f32, view_state: &mut ViewState, light: &mut Light) -> bool {
+        let Some((camera, sampled_light)) = self.path.sample(self.time) else {
+            return false;
+        };
+        camera.apply(view_state);
+        *light = sampled_light;
+        self.time += delta_time;
+        self.time <= self.path.duration() + delta_time
+    }
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1 + (p2 - p0) * t + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2 + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[derive(Clone, Copy)]
+struct Quaternion {
+    w: f32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+impl Quaternion {
+    // The camera turns by its pitch around x first and then by its yaw around y
+    fn from_camera(camera: &Camera) -> Quaternion {
+        let (sin_yaw, cos_yaw) = (camera.yaw / 2.0).sin_cos();
+        let (sin_pitch, cos_pitch) = (camera.pitch / 2.0).sin_cos();
+        Quaternion {
+            w: cos_yaw * cos_pitch,
+            x: cos_yaw * sin_pitch,
+            y: sin_yaw * cos_pitch,
+            z: -sin_yaw * sin_pitch,
+        }
+    }
+
+    fn rotate(&self, v: (f32, f32, f32)) -> (f32, f32, f32) {
+        let cross = |a: (f32, f32, f32), b: (f32, f32, f32)| (a.1 * b.2 - a.2 * b.1, a.2 * b.0 - a.0 * b.2, a.0 * b.1 - a.1 * b.0);
+        let q = (self.x, self.y, self.z);
+        let t = cross(q, v);
+        let t = (2.0 * t.0, 2.0 * t.1, 2.0 * t.2);
+        let u = cross(q, t);
+        (v.0 + self.w * t.0 + u.0, v.1 + self.w * t.1 + u.1, v.2 + self.w * t.2 + u.2)
+    }
+
+    fn yaw_pitch(&self) -> (f32, f32) {
+        let (fx, fy, fz) = self.rotate((0.0, 0.0, -1.0));
+        ((-fx).atan2(-fz).rem_euclid(2.0 * std::f32::consts::PI), fy.clamp(-1.0, 1.0).asin())
+    }
+
+    fn slerp(self, other: Quaternion, t: f32) -> Quaternion {
+        let mut dot = self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z;
+        let mut other = other;
+        if dot < 0.0 {
+            other = Quaternion { w: -other.w, x: -other.x, y: -other.y, z: -other.z };
+            dot = -dot;
+        }
+
+        let (a, b) = if dot > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = dot.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        let q = Quaternion {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        };
+        let length = (q.w * q.w + q.x * q.x + q.y * q.y + q.z * q.z).sqrt();
+        Quaternion { w: q.w / length, x: q.x / length, y: q.y / length, z: q.z / length }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, x: f32, yaw: f32, pitch: f32) -> Keyframe {
+        Keyframe {
+            time,
+            camera: Camera { eye: (x, 2.0 * x, 700.0 - x), yaw, pitch },
+            light: Light { x: -x, y: 0.0, z: 700.0, intensity: 100.0 + x },
+        }
+    }
+
+    #[test]
+    fn sampling_passes_through_keyframes() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let mut path = CameraPath::new(interpolation);
+            path.push(keyframe(0.0, 0.0, 0.0, 0.0));
+            path.push(keyframe(1.0, 50.0, 0.5, 0.2));
+            path.push(keyframe(3.0, 20.0, 1.5, -0.4));
+
+            for k in path.keyframes().to_vec() {
+                let (camera, light) = path.sample(k.time).unwrap();
+                assert!((camera.eye.0 - k.camera.eye.0).abs() < 1e-3);
+                assert!((camera.eye.2 - k.camera.eye.2).abs() < 1e-3);
+                assert!((camera.yaw - k.camera.yaw).abs() < 1e-4);
+                assert!((camera.pitch - k.camera.pitch).abs() < 1e-4);
+                assert!((light.intensity - k.light.intensity).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn orientation_turns_along_the_shortest_arc() {
+        let mut path = CameraPath::new(Interpolation::Linear);
+        path.push(keyframe(0.0, 0.0, 0.2, 0.0));
+        path.push(keyframe(1.0, 0.0, 2.0 * std::f32::consts::PI - 0.2, 0.0));
+
+        let (camera, _) = path.sample(0.5).unwrap();
+        let yaw = (camera.yaw + std::f32::consts::PI).rem_euclid(2.0 * std::f32::consts::PI) - std::f32::consts::PI;
+        assert!(yaw.abs() < 1e-4);
+        assert!(camera.pitch.abs() < 1e-4);
+    }
+
+    #[test]
+    fn linear_positions_are_proportional_to_time() {
+        let mut path = CameraPath::new(Interpolation::Linear);
+        path.push(keyframe(0.0, 0.0, 0.0, 0.0));
+        path.push(keyframe(2.0, 40.0, 0.0, 0.0));
+
+        let (camera, light) = path.sample(0.5).unwrap();
+        assert!((camera.eye.0 - 10.0).abs() < 1e-4);
+        assert!((light.x + 10.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,388 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::graphics::operations::Operations;
+use crate::types::{object::Object, pixel::{Pixel, OVERLAY_ID}, renderer::Renderer, view_state::ViewState};
+use super::gizmo::{generate_gizmo_pixels, Axis, GizmoMode};
+
+const ROTATE_SPEED: f32 = 0.01;
+const SCALE_SPEED: f32 = 0.005;
+const TRANSLATE_SNAP: f32 = 5.0;
+const ROTATE_SNAP: f32 = std::f32::consts::PI / 12.0;
+const SCALE_SNAP: f32 = 0.1;
+
+#[derive(Clone, Copy)]
+struct Transform {
+    x: f32,
+    y: f32,
+    z: f32,
+    angle_x: f32,
+    angle_y: f32,
+    angle_z: f32,
+    scale_x: f32,
+    scale_y: f32,
+    scale_z: f32,
+}
+
+struct Entry {
+    object: Object,
+    radius: f32,
+}
+
+/// Owns the editable objects and applies gizmo drags to the selected one.
+/// Objects keep their pixels relative to their own center; `sync` sends the transformed pixels to the renderer.
+pub struct Editor {
+    entries: Vec<Entry>,
+    selected: Option<usize>,
+    mode: GizmoMode,
+    axis: Option<Axis>,
+    drag_start: Option<Transform>,
+    drag_delta: (f32, f32),
+    dirty: Vec<u32>,
+    gizmo_dirty: bool,
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor {
+            entries: Vec::new(),
+            selected: None,
+            mode: GizmoMode::Translate,
+            axis: None,
+            drag_start: None,
+            drag_delta: (0.0, 0.0),
+            dirty: Vec::new(),
+            gizmo_dirty: false,
+        }
+    }
+
+    /// Adds an object from pixels in scene coordinates, placing its origin at their center.
+    pub fn add_object(&mut self, id: u32, mut pixels: Vec<Pixel>) {
+        let count = pixels.len().max(1) as f32;
+        let center = pixels.iter().fold((0.0, 0.0, 0.0), |c, p| (c.0 + p.x, c.1 + p.y, c.2 + p.z));
+        let center = (center.0 / count, center.1 / count, center.2 / count);
+
+        let mut radius: f32 = 0.0;
+        for pixel in pixels.iter_mut() {
+            pixel.x -= center.0;
+            pixel.y -= center.1;
+            pixel.z -= center.2;
+            radius = radius.max((pixel.x * pixel.x + pixel.y * pixel.y + pixel.z * pixel.z).sqrt());
+        }
+
+        self.entries.push(Entry {
+            object: Object {
+                id,
+                x: center.0,
+                y: center.1,
+                z: center.2,
+                angle_x: 0.0,
+                angle_y: 0.0,
+                angle_z: 0.0,
+                scale_x: 1.0,
+                scale_y: 1.0,
+                scale_z: 1.0,
+                pixels,
+            },
+            radius,
+        });
+        self.dirty.push(id);
+    }
+
+    /// Selects an editable object, or clears the selection for anything else. Clicks on the gizmo keep it.
+    pub fn select(&mut self, id: Option<u32>) {
+        if id == Some(OVERLAY_ID) {
+            return;
+        }
+        self.selected = id.and_then(|id| self.entries.iter().position(|entry| entry.object.id == id));
+        self.end_drag();
+        self.gizmo_dirty = true;
+    }
+
+    pub fn selected_id(&self) -> Option<u32> {
+        self.selected.map(|index| self.entries[index].object.id)
+    }
+
+    pub fn set_mode(&mut self, mode: GizmoMode) {
+        self.mode = mode;
+        self.gizmo_dirty = true;
+    }
+
+    /// Constrains drags to an axis, or lifts the constraint when it is set already.
+    pub fn toggle_axis(&mut self, axis: Axis) {
+        self.axis = if self.axis == Some(axis) { None } else { Some(axis) };
+        self.gizmo_dirty = true;
+    }
+
+    /// Applies a mouse drag to the selected object. Drags accumulate from the start of the gesture,
+    /// so snapping lands on the same values regardless of how the motion was split into events.
+    pub fn drag(&mut self, dx: i32, dy: i32, snap: bool, view_state: &ViewState) {
+        let Some(index) = self.selected else { return; };
+        let object = &mut self.entries[index].object;
+        let start = *self.drag_start.get_or_insert(Transform {
+            x: object.x,
+            y: object.y,
+            z: object.z,
+            angle_x: object.angle_x,
+            angle_y: object.angle_y,
+            angle_z: object.angle_z,
+            scale_x: object.scale_x,
+            scale_y: object.scale_y,
+            scale_z: object.scale_z,
+        });
+        self.drag_delta.0 += dx as f32;
+        self.drag_delta.1 += dy as f32;
+        let (dx, dy) = self.drag_delta;
+        // Only the components a drag can change snap, the others keep their value
+        let snaps = |axis: Axis| snap && self.axis.is_none_or(|constrained| constrained == axis);
+
+        match self.mode {
+            GizmoMode::Translate => {
+                let units = screen_units(view_state, (start.x, start.y, start.z));
+                let mut delta = view_to_scene(view_state, (dx * units, -dy * units, 0.0));
+                if let Some(axis) = self.axis {
+                    let (ax, ay, az) = axis.direction();
+                    let along = delta.0 * ax + delta.1 * ay + delta.2 * az;
+                    delta = (ax * along, ay * along, az * along);
+                }
+                object.x = snapped(start.x + delta.0, TRANSLATE_SNAP, snaps(Axis::X));
+                object.y = snapped(start.y + delta.1, TRANSLATE_SNAP, snaps(Axis::Y));
+                object.z = snapped(start.z + delta.2, TRANSLATE_SNAP, snaps(Axis::Z));
+            }
+            GizmoMode::Rotate => {
+                let (angle_x, angle_y, angle_z) = match self.axis {
+                    None => (dy * ROTATE_SPEED, dx * ROTATE_SPEED, 0.0),
+                    Some(Axis::X) => ((dx - dy) * ROTATE_SPEED, 0.0, 0.0),
+                    Some(Axis::Y) => (0.0, (dx - dy) * ROTATE_SPEED, 0.0),
+                    Some(Axis::Z) => (0.0, 0.0, (dx - dy) * ROTATE_SPEED),
+                };
+                object.angle_x = snapped(start.angle_x + angle_x, ROTATE_SNAP, snaps(Axis::X)).rem_euclid(2.0 * std::f32::consts::PI);
+                object.angle_y = snapped(start.angle_y + angle_y, ROTATE_SNAP, snaps(Axis::Y)).rem_euclid(2.0 * std::f32::consts::PI);
+                object.angle_z = snapped(start.angle_z + angle_z, ROTATE_SNAP, snaps(Axis::Z)).rem_euclid(2.0 * std::f32::consts::PI);
+            }
+            GizmoMode::Scale => {
+                let factor = (1.0 + (dx - dy) * SCALE_SPEED).max(0.01);
+                let (fx, fy, fz) = match self.axis {
+                    None => (factor, factor, factor),
+                    Some(Axis::X) => (factor, 1.0, 1.0),
+                    Some(Axis::Y) => (1.0, factor, 1.0),
+                    Some(Axis::Z) => (1.0, 1.0, factor),
+                };
+                object.scale_x = snapped(start.scale_x * fx, SCALE_SNAP, snaps(Axis::X)).max(SCALE_SNAP / 10.0);
+                object.scale_y = snapped(start.scale_y * fy, SCALE_SNAP, snaps(Axis::Y)).max(SCALE_SNAP / 10.0);
+                object.scale_z = snapped(start.scale_z * fz, SCALE_SNAP, snaps(Axis::Z)).max(SCALE_SNAP / 10.0);
+            }
+        }
+
+        if !self.dirty.contains(&object.id) {
+            self.dirty.push(object.id);
+        }
+        self.gizmo_dirty = true;
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag_start = None;
+        self.drag_delta = (0.0, 0.0);
+    }
+
+    /// Marks every object and the gizmo as changed, for a renderer that has none of them yet.
+    pub fn resync(&mut self) {
+        self.dirty = self.entries.iter().map(|entry| entry.object.id).collect();
+        self.gizmo_dirty = true;
+    }
+
+    /// Sends changed objects and the gizmo to the renderer.
+    pub fn sync(&mut self, renderer: &mut dyn Renderer<'_>) {
+        for id in self.dirty.drain(..) {
+            if let Some(entry) = self.entries.iter().find(|entry| entry.object.id == id) {
+                renderer.update_object(id, transformed_pixels(&entry.object));
+            }
+        }
+
+        if self.gizmo_dirty {
+            match self.selected {
+                Some(index) => {
+                    let entry = &self.entries[index];
+                    let object = &entry.object;
+                    let scale = object.scale_x.max(object.scale_y).max(object.scale_z);
+                    let length = (entry.radius * scale * 1.2).max(1.0);
+                    renderer.update_object(OVERLAY_ID, generate_gizmo_pixels(self.mode, self.axis, (object.x, object.y, object.z), length));
+                }
+                None => renderer.remove_object(OVERLAY_ID),
+            }
+            self.gizmo_dirty = false;
+        }
+    }
+
+    /// Writes the transforms of all objects, one object per line.
+    pub fn save_scene(&self, path: &Path) -> io::Result<()> {
+        let mut scene = String::from("# id x y z angle_x angle_y angle_z scale_x scale_y scale_z\n");
+        for entry in &self.entries {
+            let o = &entry.object;
+            scene += &format!(
+                "{} {} {} {} {} {} {} {} {} {}\n",
+                o.id, o.x, o.y, o.z, o.angle_x, o.angle_y, o.angle_z, o.scale_x, o.scale_y, o.scale_z
+            );
+        }
+        fs::write(path, scene)
+    }
+
+    /// Applies transforms saved by `save_scene` to the objects with matching ids.
+    pub fn load_scene(&mut self, path: &Path) -> io::Result<()> {
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid scene line: {}", line));
+            let values: Vec<&str> = line.split_whitespace().collect();
+            if values.len() != 10 {
+                return Err(invalid());
+            }
+            let id: u32 = values[0].parse().map_err(|_| invalid())?;
+            let mut floats = [0.0; 9];
+            for (value, text) in floats.iter_mut().zip(&values[1..]) {
+                *value = text.parse().map_err(|_| invalid())?;
+            }
+
+            if let Some(entry) = self.entries.iter_mut().find(|entry| entry.object.id == id) {
+                let o = &mut entry.object;
+                [o.x, o.y, o.z, o.angle_x, o.angle_y, o.angle_z, o.scale_x, o.scale_y, o.scale_z] = floats;
+                if !self.dirty.contains(&id) {
+                    self.dirty.push(id);
+                }
+            }
+        }
+        self.gizmo_dirty = true;
+        Ok(())
+    }
+}
+
+fn transformed_pixels(object: &Object) -> Vec<Pixel> {
+    let size = object.scale_x.max(object.scale_y).max(object.scale_z);
+    object.pixels.iter().map(|pixel| {
+        let (x, y, z) = Operations::rotate(
+            (pixel.x * object.scale_x, pixel.y * object.scale_y, pixel.z * object.scale_z),
+            (object.angle_x, object.angle_y, object.angle_z)
+        );
+        Pixel {
+            x: x + object.x,
+            y: y + object.y,
+            z: z + object.z,
+            size_factor: pixel.size_factor * size,
+            ..*pixel
+        }
+    }).collect()
+}
+
+fn snapped(value: f32, step: f32, snap: bool) -> f32 {
+    if snap { (value / step).round() * step } else { value }
+}
+
+/// Scene units covered by one screen pixel at a scene position, following the projection shader.
+fn screen_units(view_state: &ViewState, position: (f32, f32, f32)) -> f32 {
+    let (x, y, z) = Operations::rotate(position, (view_state.angle_x, view_state.angle_y, view_state.angle_z));
+    let (_, _, z) = Operations::rotate(
+        (x + view_state.ref_x, y + view_state.ref_y, z + view_state.ref_z - view_state.camera_z),
+        (-view_state.c_angle_x, -view_state.c_angle_y, -view_state.c_angle_z)
+    );
+    (-z).max(view_state.z_offset) / view_state.scale
+}
+
+/// Turns a direction on screen into the scene direction it shows, undoing the camera and view rotations.
+fn view_to_scene(view_state: &ViewState, v: (f32, f32, f32)) -> (f32, f32, f32) {
+    let v = Operations::rotate_inverse(v, (-view_state.c_angle_x, -view_state.c_angle_y, -view_state.c_angle_z));
+    Operations::rotate_inverse(v, (view_state.angle_x, view_state.angle_y, view_state.angle_z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::test_helper::test_view_state;
+
+    fn editor() -> Editor {
+        let mut editor = Editor::new();
+        for (id, center) in [(1, (1.3, 2.0, -7.2)), (2, (40.0, -3.7, 11.1))] {
+            let pixels = [(-1.0, 0.0, 0.0), (1.0, 0.0, 0.0), (0.0, 2.0, 0.0), (0.0, -2.0, 1.0)].iter().map(|&(x, y, z)| Pixel {
+                id, x: center.0 + x, y: center.1 + y, z: center.2 + z, r: 1.0, g: 1.0, b: 1.0, a: 1.0, size_factor: 1.0,
+            }).collect();
+            editor.add_object(id, pixels);
+        }
+        editor
+    }
+
+    fn on_step(value: f32, step: f32) -> bool {
+        (value / step - (value / step).round()).abs() < 1e-3
+    }
+
+    #[test]
+    fn snapped_drags_land_on_whole_steps_along_the_constrained_axis() {
+        let view_state = test_view_state();
+        let mut editor = editor();
+        editor.select(Some(1));
+        editor.toggle_axis(Axis::X);
+        let o = &editor.entries[0].object;
+        let (start, off_axis) = (o.x, (o.y, o.z));
+        assert!(!on_step(off_axis.0, TRANSLATE_SNAP) && !on_step(off_axis.1, TRANSLATE_SNAP));
+        for (dx, dy) in [(7, 3), (11, -2), (5, 0)] {
+            editor.drag(dx, dy, true, &view_state);
+            let o = &editor.entries[0].object;
+            assert!(on_step(o.x, TRANSLATE_SNAP), "{}", o.x);
+            // The constraint keeps the drag and its snapping off the other axes
+            assert_eq!((o.y, o.z), off_axis);
+        }
+        let moved = editor.entries[0].object.x;
+        assert!((moved - start).abs() >= TRANSLATE_SNAP, "{} did not move from {}", moved, start);
+
+        // The same gesture in one event lands on the same value
+        editor.end_drag();
+        editor.entries[0].object.x = start;
+        editor.drag(23, 1, true, &view_state);
+        assert_eq!(editor.entries[0].object.x, moved);
+
+        editor.end_drag();
+        editor.set_mode(GizmoMode::Rotate);
+        editor.toggle_axis(Axis::Z);
+        for (dx, dy) in [(13, 0), (9, -4), (30, 2)] {
+            editor.drag(dx, dy, true, &view_state);
+            let o = &editor.entries[0].object;
+            assert_eq!((o.angle_x, o.angle_y), (0.0, 0.0));
+            assert!(on_step(o.angle_z, ROTATE_SNAP), "{}", o.angle_z);
+        }
+        assert!(editor.entries[0].object.angle_z > 0.0);
+        // The other object is left alone
+        assert_eq!((editor.entries[1].object.x, editor.entries[1].object.angle_z), (40.0, 0.0));
+    }
+
+    #[test]
+    fn saved_scenes_load_back_identically() {
+        let view_state = test_view_state();
+        let mut edited = editor();
+        for (id, mode) in [(1, GizmoMode::Translate), (2, GizmoMode::Rotate), (2, GizmoMode::Scale)] {
+            edited.select(Some(id));
+            edited.set_mode(mode);
+            edited.drag(17, -9, false, &view_state);
+            edited.end_drag();
+        }
+        let path = std::env::temp_dir().join("editor_round_trip.txt");
+        edited.save_scene(&path).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+
+        let mut loaded = editor();
+        loaded.load_scene(&path).unwrap();
+        for (a, b) in edited.entries.iter().zip(&loaded.entries) {
+            let (a, b) = (&a.object, &b.object);
+            assert_eq!(
+                [a.x, a.y, a.z, a.angle_x, a.angle_y, a.angle_z, a.scale_x, a.scale_y, a.scale_z],
+                [b.x, b.y, b.z, b.angle_x, b.angle_y, b.angle_z, b.scale_x, b.scale_y, b.scale_z],
+            );
+        }
+        loaded.save_scene(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), saved);
+
+        fs::write(&path, "1 0 0 0").unwrap();
+        assert_eq!(loaded.load_scene(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let _ = fs::remove_file(&path);
+    }
+}
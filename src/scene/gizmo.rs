@@ -0,0 +1,82 @@
+use crate::types::pixel::{Pixel, OVERLAY_ID};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn direction(self) -> (f32, f32, f32) {
+        match self {
+            Axis::X => (1.0, 0.0, 0.0),
+            Axis::Y => (0.0, 1.0, 0.0),
+            Axis::Z => (0.0, 0.0, 1.0),
+        }
+    }
+}
+
+const AXES: [(Axis, [f32; 3]); 3] = [
+    (Axis::X, [1.0, 0.2, 0.2]),
+    (Axis::Y, [0.2, 1.0, 0.2]),
+    (Axis::Z, [0.2, 0.4, 1.0]),
+];
+const ACTIVE_COLOR: [f32; 3] = [1.0, 0.9, 0.1];
+const STEPS: usize = 96;
+
+/// Generates the gizmo of a mode around `center` as overlay pixels, with the constrained axis highlighted.
+pub fn generate_gizmo_pixels(mode: GizmoMode, active: Option<Axis>, center: (f32, f32, f32), length: f32) -> Vec<Pixel> {
+    let step = length / STEPS as f32;
+    let mut pixels = Vec::new();
+
+    for (axis, color) in AXES {
+        let color = if active == Some(axis) { ACTIVE_COLOR } else { color };
+        let (dx, dy, dz) = axis.direction();
+        let mut push = |x: f32, y: f32, z: f32, size_factor: f32| pixels.push(Pixel {
+            id: OVERLAY_ID,
+            x: center.0 + x,
+            y: center.1 + y,
+            z: center.2 + z,
+            r: color[0],
+            g: color[1],
+            b: color[2],
+            a: 1.0,
+            size_factor,
+        });
+
+        match mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                for i in 0..=STEPS {
+                    let t = i as f32 * step;
+                    push(dx * t, dy * t, dz * t, step * 1.5);
+                }
+                // Arrow tips for moving, box tips for scaling
+                let tip_size = if mode == GizmoMode::Translate { step * 4.0 } else { step * 6.0 };
+                push(dx * (length + step * 2.0), dy * (length + step * 2.0), dz * (length + step * 2.0), tip_size);
+            }
+            GizmoMode::Rotate => {
+                // A ring in the plane the axis is normal to
+                let radius = length * 0.8;
+                for i in 0..STEPS * 2 {
+                    let angle = i as f32 / (STEPS * 2) as f32 * 2.0 * std::f32::consts::PI;
+                    let (c, s) = (angle.cos() * radius, angle.sin() * radius);
+                    match axis {
+                        Axis::X => push(0.0, c, s, step * 1.5),
+                        Axis::Y => push(c, 0.0, s, step * 1.5),
+                        Axis::Z => push(c, s, 0.0, step * 1.5),
+                    }
+                }
+            }
+        }
+    }
+
+    pixels
+}
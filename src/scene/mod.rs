@@ -0,0 +1,4 @@
+pub mod editor;
+pub mod gizmo;
+pub mod camera_path;
+pub mod bookmarks;
@@ -0,0 +1,141 @@
+use crate::graphics::operations::Operations;
+use super::view_state::ViewState;
+
+// Keeps the camera from flipping over when looking straight up or down
+pub const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+// The least share of the view direction along the scene's z axis that the view state can place the eye for
+const MIN_DEPTH_AXIS: f32 = 0.01;
+
+/// Camera input gathered over a frame.
+#[derive(Clone, Copy, Default)]
+pub struct CameraInput {
+    /// Velocity in units per second to the right, up and backward.
+    pub movement: (f32, f32, f32),
+    /// Turn in radians of yaw and pitch asked for by camera drags and sticks.
+    pub look: (f32, f32),
+    /// Turn in radians of yaw and pitch from moving the mouse without a camera drag.
+    pub pointer: (f32, f32),
+}
+
+/// Where the camera is and where it looks, in the space of the scene after its rotation and `ref` offset.
+#[derive(Clone, Copy)]
+pub struct Camera {
+    pub eye: (f32, f32, f32),
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Camera {
+    pub fn from_view_state(view_state: &ViewState) -> Camera {
+        let c_angles = (-view_state.c_angle_x, -view_state.c_angle_y, -view_state.c_angle_z);
+        let (x, y, z) = Operations::rotate_inverse((-view_state.camera_x, -view_state.camera_y, 0.0), c_angles);
+        let (fx, fy, fz) = Operations::rotate_inverse((0.0, 0.0, -1.0), c_angles);
+        Camera {
+            eye: (x, y, z + view_state.camera_z),
+            yaw: (-fx).atan2(-fz),
+            pitch: fy.clamp(-1.0, 1.0).asin(),
+        }
+    }
+
+    /// Writes the camera into the camera angles and offsets of the view state.
+    pub fn apply(&self, view_state: &mut ViewState) {
+        // The shaders rotate by the camera angles in x, y, z order, so the yaw-then-pitch rotation
+        // of the camera is converted to those angles
+        let (yaw, pitch) = self.expressible_angles();
+        let rotation = |v| Operations::rotate(Operations::rotate(v, (0.0, -yaw, 0.0)), (-pitch, 0.0, 0.0));
+        let column_x = rotation((1.0, 0.0, 0.0));
+        let column_y = rotation((0.0, 1.0, 0.0));
+        let column_z = rotation((0.0, 0.0, 1.0));
+        let angle_x = column_y.2.atan2(column_z.2);
+        let angle_y = -column_x.2.clamp(-1.0, 1.0).asin();
+        let angle_z = column_x.1.atan2(column_x.0);
+        view_state.c_angle_x = (-angle_x).rem_euclid(2.0 * std::f32::consts::PI);
+        view_state.c_angle_y = (-angle_y).rem_euclid(2.0 * std::f32::consts::PI);
+        view_state.c_angle_z = (-angle_z).rem_euclid(2.0 * std::f32::consts::PI);
+
+        // The eye sits where the view offset cancels, solved along the view's z axis
+        let eye = rotation(self.eye);
+        let axis = column_z;
+        view_state.camera_z = eye.2 / axis.2;
+        view_state.camera_x = view_state.camera_z * axis.0 - eye.0;
+        view_state.camera_y = view_state.camera_z * axis.1 - eye.1;
+    }
+
+    /// The yaw and pitch of the nearest view direction the view state can express. `camera_z` moves the eye
+    /// along the scene's z axis, so a direction square to it, as at a yaw of 90 degrees, leaves the eye's depth
+    /// unreachable. Such directions are tilted towards z until `MIN_DEPTH_AXIS` of them lies along it.
+    fn expressible_angles(&self) -> (f32, f32) {
+        let (fx, fy, fz) = self.forward();
+        if fz.abs() >= MIN_DEPTH_AXIS {
+            return (self.yaw, self.pitch);
+        }
+        let fz = if fz > 0.0 { MIN_DEPTH_AXIS } else { -MIN_DEPTH_AXIS };
+        let k = (1.0 - MIN_DEPTH_AXIS * MIN_DEPTH_AXIS).sqrt() / (fx * fx + fy * fy).sqrt();
+        ((-fx * k).atan2(-fz), (fy * k).clamp(-1.0, 1.0).asin())
+    }
+
+    pub fn forward(&self) -> (f32, f32, f32) {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        (-sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+
+    pub fn right(&self) -> (f32, f32, f32) {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        (cos_yaw, 0.0, -sin_yaw)
+    }
+
+    pub fn up(&self) -> (f32, f32, f32) {
+        let (fx, fy, fz) = self.forward();
+        let (rx, ry, rz) = self.right();
+        (ry * fz - rz * fy, rz * fx - rx * fz, rx * fy - ry * fx)
+    }
+
+    pub fn turn(&mut self, (yaw, pitch): (f32, f32)) {
+        self.yaw = (self.yaw + yaw).rem_euclid(2.0 * std::f32::consts::PI);
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+}
+
+/// Turns frame input into camera movement. Controllers can be swapped at runtime, each one
+/// taking over the camera where the previous one left it.
+pub trait CameraController {
+    fn name(&self) -> &'static str;
+    fn attach(&mut self, view_state: &ViewState);
+    fn update(&mut self, input: &CameraInput, delta_time: f32) -> Camera;
+}
+
+pub fn offset(p: (f32, f32, f32), direction: (f32, f32, f32), distance: f32) -> (f32, f32, f32) {
+    (p.0 + direction.0 * distance, p.1 + direction.1 * distance, p.2 + direction.2 * distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, PI};
+    use crate::helpers::test_helper::test_view_state;
+
+    fn dot(a: (f32, f32, f32), b: (f32, f32, f32)) -> f32 {
+        a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+    }
+
+    #[test]
+    fn cameras_round_trip_through_the_view_state() {
+        for yaw in [0.0, 1.0, -2.5, PI, FRAC_PI_2, -FRAC_PI_2, FRAC_PI_2 + 0.004] {
+            for pitch in [0.0, 0.7, -1.2, MAX_PITCH, -MAX_PITCH] {
+                let camera = Camera { eye: (30.0, -20.0, 250.0), yaw, pitch };
+                let mut view_state = test_view_state();
+                camera.apply(&mut view_state);
+                assert!(view_state.camera_z.is_finite() && view_state.camera_z.abs() < 1e5, "{} {}: {}", yaw, pitch, view_state.camera_z);
+
+                let back = Camera::from_view_state(&view_state);
+                let d = (back.eye.0 - camera.eye.0, back.eye.1 - camera.eye.1, back.eye.2 - camera.eye.2);
+                assert!(dot(d, d).sqrt() < 0.05, "{} {}: eye {:?}", yaw, pitch, back.eye);
+                // Directions square to the scene's z axis are tilted by less than a degree, the others come back as they were
+                let turned = dot(back.forward(), camera.forward()).clamp(-1.0, 1.0).acos();
+                let limit = if camera.forward().2.abs() < MIN_DEPTH_AXIS { MIN_DEPTH_AXIS * 1.1 } else { 1e-3 };
+                assert!(turned < limit, "{} {}: turned by {}", yaw, pitch, turned);
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Everything the engine can fail at, for applications to recover from or report.
+#[derive(Debug)]
+pub enum EngineError {
+    /// SDL or one of its subsystems could not be started.
+    Init(String),
+    /// Creating the window, its canvas or its textures, or presenting to it, failed.
+    Window(String),
+    /// No usable adapter or device, or the GPU failed to run or read back a frame.
+    Gpu(String),
+    /// A model, texture or data file could not be read or decoded.
+    Asset { path: PathBuf, message: String },
+    /// Writing output such as frames, screenshots or chunks failed.
+    Io(io::Error),
+}
+
+impl EngineError {
+    /// Wraps an error from loading the file, for `map_err`.
+    pub fn asset<E: fmt::Display>(path: &Path) -> impl FnOnce(E) -> EngineError + '_ {
+        move |error| EngineError::Asset { path: path.to_path_buf(), message: error.to_string() }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Init(message) => write!(f, "initialization failed: {}", message),
+            EngineError::Window(message) => write!(f, "window error: {}", message),
+            EngineError::Gpu(message) => write!(f, "gpu error: {}", message),
+            EngineError::Asset { path, message } => write!(f, "{}: {}", path.display(), message),
+            EngineError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EngineError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EngineError {
+    fn from(error: io::Error) -> EngineError {
+        EngineError::Io(error)
+    }
+}
@@ -1,4 +1,20 @@
 pub enum EventCallback {
     Quit,
     Resized(u32, u32),
+    Pick(i32, i32),
+    SaveScene,
+    CycleCamera,
+    RecordPath,
+    PlayPath,
+    Screenshot,
+    SwitchBackend,
+    ToggleOverlay,
+    ToggleShadows,
+    ToggleLighting,
+    CycleDebugView,
+    Bookmark(usize),
+    SaveBookmark(usize),
+    // The name of a game controller that was plugged in or out
+    ControllerConnected(String),
+    ControllerDisconnected(String),
 }
@@ -0,0 +1,8 @@
+/// What became of the pixels in the last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Pixels that ended up drawn on at least one screen position.
+    pub visible: usize,
+    /// Pixels behind the near plane or entirely off screen.
+    pub culled: usize,
+}
@@ -1,3 +1,7 @@
+/// How much of its color a pixel hidden from the light keeps, in both backends.
+pub const SHADOW_FACTOR: f32 = 0.3;
+
+#[derive(Clone, Copy)]
 pub struct Light {
     pub x: f32,
     pub y: f32,
@@ -5,3 +5,9 @@ pub mod uniforms;
 pub mod light;
 pub mod event_callback;
 pub mod renderer;
+pub mod pick;
+pub mod camera_controller;
+pub mod engine_error;
+pub mod render_settings;
+pub mod frame_stats;
+pub mod overlay_image;
@@ -8,5 +8,8 @@ pub struct Object {
     pub angle_x: f32,
     pub angle_y: f32,
     pub angle_z: f32,
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub scale_z: f32,
     pub pixels: Vec<Pixel>
 }
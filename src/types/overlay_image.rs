@@ -0,0 +1,20 @@
+/// An opaque image drawn over the frame at a fixed screen position, one texel per screen pixel
+/// packed like the frame itself, red in the highest byte and alpha in the lowest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverlayImage {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<u32>,
+}
+
+impl OverlayImage {
+    /// The columns and rows that fall inside a frame of the given size.
+    pub fn visible_size(&self, frame_width: u32, frame_height: u32) -> (u32, u32) {
+        (
+            self.width.min(frame_width.saturating_sub(self.x)),
+            self.height.min(frame_height.saturating_sub(self.y)),
+        )
+    }
+}
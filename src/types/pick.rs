@@ -0,0 +1,8 @@
+pub struct Pick {
+    pub object_id: u32,
+    pub pixel_index: usize,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub depth: f32,
+}
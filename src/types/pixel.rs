@@ -1,5 +1,8 @@
 use bytemuck::{Pod, Zeroable};
 
+/// Pixels with this id are overlays, drawn unlit on top of everything and casting no shadows.
+pub const OVERLAY_ID: u32 = u32::MAX - 1;
+
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct Pixel {
@@ -0,0 +1,79 @@
+/// Which parts of the frame are drawn, switched at runtime from the debug overlay.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub shadows: bool,
+    pub lighting: bool,
+    pub debug_view: DebugView,
+    /// Counts visible and culled pixels each frame, for `frame_stats`.
+    pub stats: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings { shadows: true, lighting: true, debug_view: DebugView::Shaded, stats: false }
+    }
+}
+
+// Bits of the shaders' uniforms.flags
+pub const FLAG_SHADOWS: u32 = 1;
+pub const FLAG_LIGHTING: u32 = 2;
+pub const FLAG_STATS: u32 = 4;
+
+impl RenderSettings {
+    pub fn flags(&self) -> u32 {
+        (self.shadows as u32 * FLAG_SHADOWS) | (self.lighting as u32 * FLAG_LIGHTING) | (self.stats as u32 * FLAG_STATS)
+    }
+}
+
+/// What the frame shows in place of the shaded colors, numbered as in the shaders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum DebugView {
+    Shaded = 0,
+    /// Distance from the camera in grays, the nearest drawn pixel white.
+    Depth = 1,
+    /// A false color per object.
+    ObjectId = 2,
+    /// The depth seen from the light, which the shadow pass tests against.
+    ShadowMap = 3,
+    /// Lit pixels white and shadowed ones dark blue.
+    ShadowMask = 4,
+    /// Normals estimated from the depth slope, x in red, y in green and towards the camera in blue.
+    Normals = 5,
+    /// Splats drawn on each screen position, from blue for one to red for many.
+    Overdraw = 6,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 7] = [
+        DebugView::Shaded,
+        DebugView::Depth,
+        DebugView::ObjectId,
+        DebugView::ShadowMap,
+        DebugView::ShadowMask,
+        DebugView::Normals,
+        DebugView::Overdraw,
+    ];
+
+    pub fn next(self) -> DebugView {
+        let index = DebugView::ALL.iter().position(|&view| view == self).unwrap_or(0);
+        DebugView::ALL[(index + 1) % DebugView::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Shaded => "shaded",
+            DebugView::Depth => "depth",
+            DebugView::ObjectId => "object id",
+            DebugView::ShadowMap => "shadow map",
+            DebugView::ShadowMask => "shadow mask",
+            DebugView::Normals => "normals",
+            DebugView::Overdraw => "overdraw",
+        }
+    }
+
+    /// Whether the view needs the light-space depth of the shadow pass.
+    pub fn uses_shadows(self) -> bool {
+        matches!(self, DebugView::ShadowMap | DebugView::ShadowMask)
+    }
+}
@@ -1,7 +1,30 @@
-use crate::types::{light::Light, pixel::Pixel, view_state::ViewState};
+use crate::types::{engine_error::EngineError, frame_stats::FrameStats, light::Light, overlay_image::OverlayImage, pick::Pick, pixel::Pixel, render_settings::RenderSettings, view_state::ViewState};
 
 pub trait Renderer<'a> {
-    fn render(&mut self, view_state: &ViewState, light: &Light);
+    fn render(&mut self, view_state: &ViewState, light: &Light) -> Result<(), EngineError>;
     fn load_pixels(&mut self, new_pixels: Vec<Pixel>);    
-    fn resize(&mut self, width: u32, height: u32);
-}
\ No newline at end of file
+    fn add_object(&mut self, id: u32, pixels: Vec<Pixel>);
+    fn update_object(&mut self, id: u32, pixels: Vec<Pixel>);
+    fn remove_object(&mut self, id: u32);
+    /// Finds what was drawn at a screen position in the last rendered frame.
+    fn pick(&mut self, x: i32, y: i32) -> Option<Pick>;
+    fn set_highlight(&mut self, id: Option<u32>);
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), EngineError>;
+    /// Returns the last rendered frame as RGBA8888 texels, along with its width and height.
+    fn screenshot(&mut self) -> Result<(Vec<u8>, u32, u32), EngineError>;
+    fn backend(&self) -> String;
+    fn pixel_count(&self) -> usize;
+    /// Starts or stops timing the passes of each frame, for renderers that can.
+    fn set_profiling(&mut self, _enabled: bool) {}
+    /// How long each pass of the last frame took in milliseconds, empty when not profiling.
+    fn pass_timings(&self) -> Vec<(&'static str, f32)> {
+        Vec::new()
+    }
+    fn set_render_settings(&mut self, _settings: RenderSettings) {}
+    /// Draws the image over every following frame, until it is replaced or taken away with None.
+    fn set_overlay(&mut self, _overlay: Option<OverlayImage>) {}
+    /// Pixel counts of the last frame, when the render settings ask for them.
+    fn frame_stats(&self) -> Option<FrameStats> {
+        None
+    }
+}
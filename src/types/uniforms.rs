@@ -26,6 +26,9 @@ pub struct Uniforms {
     pub ref_y: f32,
     pub ref_z: f32,
     pub z_offset: f32,
+    pub highlight_id: u32,
+    pub flags: u32,
+    pub debug_view: u32,
 }
 
 unsafe impl NoUninit for Uniforms {}
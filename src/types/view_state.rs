@@ -1,3 +1,4 @@
+#[derive(Clone, Copy)]
 pub struct ViewState {
     pub angle_x: f32,
     pub angle_y: f32,